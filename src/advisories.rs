@@ -0,0 +1,355 @@
+use crate::args::Args;
+use crate::graph::Graph;
+use anyhow::{anyhow, Context, Error};
+use cargo_metadata::Package;
+use petgraph::graph::NodeIndex;
+use semver::VersionReq;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct AdvisoryFile {
+    advisory: AdvisoryMetadata,
+    #[serde(default)]
+    versions: Versions,
+}
+
+#[derive(Deserialize)]
+struct AdvisoryMetadata {
+    id: String,
+    package: String,
+    title: Option<String>,
+    url: Option<String>,
+    informational: Option<String>,
+    withdrawn: Option<toml::Value>,
+}
+
+#[derive(Deserialize, Default)]
+struct Versions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Advisory {
+    pub id: String,
+    pub package: String,
+    pub title: String,
+    pub url: Option<String>,
+    /// The kind of informational advisory, like `unmaintained`, rather than a vulnerability.
+    pub informational: Option<String>,
+    pub patched: Vec<String>,
+    patched_reqs: Vec<VersionReq>,
+    unaffected_reqs: Vec<VersionReq>,
+}
+
+impl Advisory {
+    fn affects(&self, package: &Package) -> bool {
+        !self
+            .patched_reqs
+            .iter()
+            .chain(&self.unaffected_reqs)
+            .any(|req| req.matches(&package.version))
+    }
+}
+
+/// A package in the graph affected by an advisory.
+pub struct Match {
+    pub advisory: Advisory,
+    pub package: NodeIndex,
+    /// The packages leading to the affected package from the root package, if it is reachable.
+    pub path: Vec<NodeIndex>,
+}
+
+/// Matches the packages in the graph against a local checkout of the RustSec advisory database.
+pub fn find(
+    args: &Args,
+    db: &Path,
+    graph: &Graph,
+    root: Option<NodeIndex>,
+) -> Result<Vec<Match>, Error> {
+    let crates = db.join("crates");
+    if !crates.is_dir() {
+        return Err(anyhow!(
+            "`{}` is not an advisory database: no `crates` directory",
+            db.display()
+        ));
+    }
+
+    let mut packages = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let package = &graph.graph[idx];
+        // advisories only cover crates.io packages
+        if package.source.as_ref().is_some_and(|s| s.is_crates_io()) {
+            packages
+                .entry(&*package.name)
+                .or_insert_with(Vec::new)
+                .push(idx);
+        }
+    }
+
+    let parents = root.map(|root| parents(graph, root)).unwrap_or_default();
+
+    let mut matches = vec![];
+    for (name, idxs) in packages {
+        let dir = crates.join(name);
+        if !dir.is_dir() {
+            continue;
+        }
+
+        for advisory in load_advisories(args, &dir)? {
+            for idx in &idxs {
+                let package = &graph.graph[*idx];
+                if advisory.package != package.name || !advisory.affects(package) {
+                    continue;
+                }
+
+                let mut path = vec![];
+                if root == Some(*idx) || parents.contains_key(idx) {
+                    let mut current = Some(*idx);
+                    while let Some(idx) = current {
+                        path.push(idx);
+                        current = parents.get(&idx).cloned();
+                    }
+                    path.reverse();
+                }
+
+                matches.push(Match {
+                    advisory: advisory.clone(),
+                    package: *idx,
+                    path,
+                });
+            }
+        }
+    }
+
+    matches.sort_by(|a, b| {
+        graph.graph[a.package]
+            .id
+            .cmp(&graph.graph[b.package].id)
+            .then_with(|| a.advisory.id.cmp(&b.advisory.id))
+    });
+    Ok(matches)
+}
+
+/// Loads the advisories for a single crate, which are either markdown files with TOML front
+/// matter or, in older checkouts, plain TOML files.
+///
+/// Advisories which can't be parsed are skipped with a warning, so that a database using a newer
+/// format than this understands can still be used.
+fn load_advisories(args: &Args, dir: &Path) -> Result<Vec<Advisory>, Error> {
+    let mut advisories = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let markdown = match path.extension().and_then(|e| e.to_str()) {
+            Some("md") => true,
+            Some("toml") => false,
+            _ => continue,
+        };
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("error reading {}", path.display()))?;
+        match parse_advisory(&contents, markdown) {
+            Ok(Some(advisory)) => advisories.push(advisory),
+            Ok(None) => {}
+            Err(e) => {
+                if !args.quiet {
+                    eprintln!(
+                        "warning: error parsing {}: {:#}, skipping it",
+                        path.display(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(advisories)
+}
+
+/// Parses an advisory, returning `None` if it has been withdrawn.
+fn parse_advisory(contents: &str, markdown: bool) -> Result<Option<Advisory>, Error> {
+    let (front_matter, title) = if markdown {
+        split_front_matter(contents)?
+    } else {
+        (contents, None)
+    };
+
+    let file = toml::from_str::<AdvisoryFile>(front_matter)?;
+    if file.advisory.withdrawn.is_some() {
+        return Ok(None);
+    }
+
+    let parse = |reqs: &[String]| {
+        reqs.iter()
+            .map(|r| VersionReq::parse(r).with_context(|| format!("error parsing version `{}`", r)))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    Ok(Some(Advisory {
+        patched_reqs: parse(&file.versions.patched)?,
+        unaffected_reqs: parse(&file.versions.unaffected)?,
+        id: file.advisory.id,
+        package: file.advisory.package,
+        title: title.or(file.advisory.title).unwrap_or_default(),
+        url: file.advisory.url,
+        informational: file.advisory.informational,
+        patched: file.versions.patched,
+    }))
+}
+
+/// Splits a markdown advisory into its fenced TOML front matter and the title from its first
+/// heading.
+fn split_front_matter(contents: &str) -> Result<(&str, Option<String>), Error> {
+    let contents = contents.trim_start();
+    let start = "```toml";
+    if !contents.starts_with(start) {
+        return Err(anyhow!("missing TOML front matter"));
+    }
+
+    let rest = &contents[start.len()..];
+    let end = rest
+        .find("\n```")
+        .ok_or_else(|| anyhow!("unterminated TOML front matter"))?;
+
+    let title = rest[end + 4..]
+        .lines()
+        .find(|l| l.starts_with("# "))
+        .map(|l| l[2..].trim().to_string());
+
+    Ok((&rest[..end], title))
+}
+
+/// Returns the parent of each package along a shortest path from the root package.
+fn parents(graph: &Graph, root: NodeIndex) -> HashMap<NodeIndex, NodeIndex> {
+    let mut parents = HashMap::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(root);
+    queue.push_back(root);
+
+    while let Some(idx) = queue.pop_front() {
+        let mut neighbors = graph.graph.neighbors(idx).collect::<Vec<_>>();
+        // ensure a consistent choice of path
+        neighbors.sort_by_key(|n| &graph.graph[*n].id);
+
+        for neighbor in neighbors {
+            if visited.insert(neighbor) {
+                parents.insert(neighbor, idx);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    parents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::package;
+    use serde_json::json;
+
+    const ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-0001"
+package = "foo"
+date = "2020-01-01"
+url = "https://example.com/advisory"
+
+[versions]
+patched = [">= 1.2.3, < 2.0.0", ">= 2.1.0"]
+unaffected = ["< 1.0.0"]
+```
+
+# Memory corruption in foo
+
+Details.
+"#;
+
+    #[test]
+    fn front_matter() {
+        let (front_matter, title) = split_front_matter(ADVISORY).unwrap();
+        assert!(front_matter.starts_with("\n[advisory]"));
+        assert!(front_matter.ends_with("unaffected = [\"< 1.0.0\"]"));
+        assert_eq!(title.as_deref(), Some("Memory corruption in foo"));
+
+        let (_, title) = split_front_matter("```toml\n[advisory]\n```\nNo heading\n").unwrap();
+        assert_eq!(title, None);
+
+        let errors = [
+            "[advisory]\n",
+            "# Title\n```toml\n```",
+            "```toml\n[advisory]\n",
+        ];
+        for contents in &errors {
+            assert!(split_front_matter(contents).is_err(), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn affected_versions() {
+        let advisory = parse_advisory(ADVISORY, true).unwrap().unwrap();
+        assert_eq!(advisory.id, "RUSTSEC-2020-0001");
+        assert_eq!(advisory.title, "Memory corruption in foo");
+        assert_eq!(advisory.informational, None);
+
+        let cases = [
+            ("0.9.0", false),
+            ("1.0.0", true),
+            ("1.2.2", true),
+            ("1.2.3", false),
+            ("1.9.0", false),
+            ("2.0.0", true),
+            ("2.0.5", true),
+            ("2.1.0", false),
+            ("3.0.0", false),
+        ];
+        for (version, expected) in &cases {
+            let package = package("foo", json!({ "version": version }));
+            assert_eq!(advisory.affects(&package), *expected, "{}", version);
+        }
+    }
+
+    #[test]
+    fn unpatched() {
+        let contents = "[advisory]\nid = \"RUSTSEC-2020-0002\"\npackage = \"foo\"\n";
+        let advisory = parse_advisory(contents, false).unwrap().unwrap();
+        assert_eq!(advisory.title, "");
+        assert!(advisory.affects(&package("foo", json!({ "version": "9.9.9" }))));
+    }
+
+    #[test]
+    fn withdrawn() {
+        let contents = "[advisory]\nid = \"RUSTSEC-2020-0003\"\npackage = \"foo\"\n\
+                        withdrawn = 2020-02-01\n";
+        assert!(parse_advisory(contents, false).unwrap().is_none());
+    }
+
+    #[test]
+    fn informational() {
+        let contents = "[advisory]\nid = \"RUSTSEC-2020-0004\"\npackage = \"foo\"\n\
+                        informational = \"unmaintained\"\ntitle = \"foo is unmaintained\"\n";
+        let advisory = parse_advisory(contents, false).unwrap().unwrap();
+        assert_eq!(advisory.informational.as_deref(), Some("unmaintained"));
+        assert_eq!(advisory.title, "foo is unmaintained");
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            "[advisory]\nid = \"RUSTSEC-2020-0005\"\n",
+            "[advisory]\nid = 5\npackage = \"foo\"\n",
+            "[advisory]\nid = \"RUSTSEC-2020-0005\"\npackage = \"foo\"\n\
+             [versions]\npatched = [\"not a version\"]\n",
+        ];
+        for contents in &cases {
+            assert!(parse_advisory(contents, false).is_err(), "{}", contents);
+        }
+    }
+}
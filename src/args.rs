@@ -16,6 +16,28 @@ pub enum Opts {
     Tree(Args),
 }
 
+#[derive(StructOpt)]
+pub enum Command {
+    #[structopt(
+    name = "serve",
+    setting = AppSettings::UnifiedHelpMessage,
+    setting = AppSettings::DeriveDisplayOrder,
+    setting = AppSettings::DontCollapseArgsInUsage
+    )]
+    /// Serve the dependency graph over HTTP for exploring in a browser
+    Serve(ServeArgs),
+}
+
+#[derive(StructOpt)]
+pub struct ServeArgs {
+    #[structopt(long = "port", value_name = "PORT", default_value = "8000")]
+    /// Port to listen on
+    pub port: u16,
+    #[structopt(long = "bind", value_name = "ADDR", default_value = "127.0.0.1")]
+    /// Address to listen on
+    pub bind: String,
+}
+
 #[derive(StructOpt)]
 pub struct Args {
     #[structopt(long = "package", short = "p", value_name = "SPEC")]
@@ -30,9 +52,9 @@ pub struct Args {
     #[structopt(long = "no-default-features")]
     /// Do not activate the `default` feature
     pub no_default_features: bool,
-    #[structopt(long = "target", value_name = "TARGET")]
-    /// Set the target triple
-    pub target: Option<String>,
+    #[structopt(long = "target", value_name = "TARGET", number_of_values = 1)]
+    /// Set the target triple (may be specified multiple times)
+    pub target: Vec<String>,
     #[structopt(long = "all-targets")]
     /// Return dependencies for all targets. By default only the host target is matched.
     pub all_targets: bool,
@@ -51,12 +73,25 @@ pub struct Args {
     #[structopt(long = "prefix-depth")]
     /// Display the dependencies as a list (rather than a tree), but prefixed with the depth
     pub prefix_depth: bool,
+    #[structopt(long = "paths")]
+    /// Display the dependencies as a list, each prefixed with the packages leading to it, with a
+    /// line for every path (implies --all)
+    pub paths: bool,
+    #[structopt(long = "depth", value_name = "DEPTH")]
+    /// Maximum depth of dependencies to display
+    pub depth: Option<usize>,
     #[structopt(long = "all", short = "a")]
     /// Don't truncate dependencies that have already been displayed
     pub all: bool,
     #[structopt(long = "duplicate", short = "d")]
     /// Show only dependencies which come in multiple versions (implies -i)
     pub duplicates: bool,
+    #[structopt(long = "highlight", value_name = "SPEC", number_of_values = 1)]
+    /// Highlight every occurrence of a package in the tree (may be specified multiple times)
+    pub highlight: Vec<String>,
+    #[structopt(long = "highlight-ancestors")]
+    /// Also highlight the packages leading to each highlighted package
+    pub highlight_ancestors: bool,
     #[structopt(long = "charset", value_name = "CHARSET", default_value = "utf8")]
     /// Character set to use in output: utf8, ascii
     pub charset: Charset,
@@ -68,6 +103,68 @@ pub struct Args {
     )]
     /// Format string used for printing dependencies
     pub format: String,
+    #[structopt(long = "output-format", value_name = "FORMAT", default_value = "tree")]
+    /// Output format: tree, cyclonedx-json, cyclonedx-xml, spdx, spdx-json, mermaid,
+    /// plantuml, html, csv, tsv
+    pub output_format: OutputFormat,
+    #[structopt(
+        long = "columns",
+        value_name = "COLUMNS",
+        default_value = "p,depth,parent,kind"
+    )]
+    /// Comma-separated columns of csv and tsv output: format placeholders like p, l, r and purl,
+    /// or depth, parent and kind
+    pub columns: String,
+    #[structopt(long = "rows", value_name = "ROWS", default_value = "packages")]
+    /// Rows of csv and tsv output: packages, edges
+    pub rows: Rows,
+    #[structopt(long = "size")]
+    /// Annotate packages with the size of their Rust sources, alone and with their exclusive
+    /// dependencies
+    pub size: bool,
+    #[structopt(long = "unsafe")]
+    /// Annotate packages with their use of unsafe code, alone and with their dependencies
+    pub unsafe_code: bool,
+    #[structopt(long = "advisories", value_name = "PATH", parse(from_os_str))]
+    /// Mark packages affected by advisories in a local copy of the RustSec advisory database
+    pub advisories: Option<PathBuf>,
+    #[structopt(long = "outdated")]
+    /// Mark packages with newer versions in the locally cached registry index
+    pub outdated: bool,
+    #[structopt(long = "stats", conflicts_with_all = &["weight", "topo", "critical-path", "unit-graph", "interactive", "output-format"])]
+    /// Print summary statistics of the dependency graph rather than the tree
+    pub stats: bool,
+    #[structopt(long = "weight", conflicts_with_all = &["topo", "critical-path", "unit-graph", "interactive", "output-format"])]
+    /// Rank direct dependencies by the number of packages only they pull in
+    pub weight: bool,
+    #[structopt(long = "topo", conflicts_with_all = &["critical-path", "unit-graph", "interactive", "output-format"])]
+    /// Print the packages in build order, grouped into levels which could build in parallel
+    pub topo: bool,
+    #[structopt(long = "critical-path", conflicts_with_all = &["unit-graph", "interactive", "output-format"])]
+    /// Print the most costly chain of normal and build dependencies from the root package
+    pub critical_path: bool,
+    #[structopt(long = "cost", value_name = "COST", default_value = "count")]
+    /// Cost of each package for --critical-path: count, lines, or timings=PATH to use the build
+    /// times from the output of `cargo build --timings=json`
+    pub cost: Cost,
+    // an optional value is modeled as a list of at most one since structopt can't combine
+    // `Option<Option<_>>` with a custom parser
+    #[structopt(
+        long = "unit-graph",
+        value_name = "PATH",
+        parse(from_os_str),
+        max_values = 1,
+        conflicts_with_all = &["interactive", "output-format"]
+    )]
+    /// Display the tree of compilation units from the output of `cargo build --unit-graph`, read
+    /// from PATH or by running it (requires nightly Cargo)
+    pub unit_graph: Option<Vec<PathBuf>>,
+    #[structopt(long = "top", value_name = "N", default_value = "10")]
+    /// Number of packages to list in rankings like those of --stats
+    pub top: usize,
+    #[structopt(long = "interactive", conflicts_with_all = &["output-format"])]
+    /// Browse the tree interactively in the terminal
+    pub interactive: bool,
     #[structopt(long = "verbose", short = "v", parse(from_occurrences))]
     /// Use verbose output (-vv very verbose/build.rs output)
     pub verbose: u32,
@@ -76,7 +173,7 @@ pub struct Args {
     pub quiet: bool,
     #[structopt(long = "color", value_name = "WHEN")]
     /// Coloring: auto, always, never
-    pub color: Option<String>,
+    pub color: Option<ColorChoice>,
     #[structopt(long = "frozen")]
     /// Require Cargo.lock and cache are up to date
     pub frozen: bool,
@@ -89,6 +186,8 @@ pub struct Args {
     #[structopt(short = "Z", value_name = "FLAG")]
     /// Unstable (nightly-only) flags to Cargo
     pub unstable_flags: Vec<String>,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
 }
 
 pub enum Charset {
@@ -107,3 +206,104 @@ impl FromStr for Charset {
         }
     }
 }
+
+pub enum OutputFormat {
+    Tree,
+    CycloneDxJson,
+    CycloneDxXml,
+    Spdx,
+    SpdxJson,
+    Mermaid,
+    PlantUml,
+    Html,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<OutputFormat, &'static str> {
+        match s {
+            "tree" => Ok(OutputFormat::Tree),
+            "cyclonedx-json" => Ok(OutputFormat::CycloneDxJson),
+            "cyclonedx-xml" => Ok(OutputFormat::CycloneDxXml),
+            "spdx" => Ok(OutputFormat::Spdx),
+            "spdx-json" => Ok(OutputFormat::SpdxJson),
+            "mermaid" => Ok(OutputFormat::Mermaid),
+            "plantuml" => Ok(OutputFormat::PlantUml),
+            "html" => Ok(OutputFormat::Html),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err("invalid output format"),
+        }
+    }
+}
+
+pub enum Rows {
+    Packages,
+    Edges,
+}
+
+impl FromStr for Rows {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Rows, &'static str> {
+        match s {
+            "packages" => Ok(Rows::Packages),
+            "edges" => Ok(Rows::Edges),
+            _ => Err("invalid rows"),
+        }
+    }
+}
+
+pub enum Cost {
+    Count,
+    Lines,
+    Timings(PathBuf),
+}
+
+impl FromStr for Cost {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Cost, &'static str> {
+        match s {
+            "count" => Ok(Cost::Count),
+            "lines" => Ok(Cost::Lines),
+            _ => match s.strip_prefix("timings=") {
+                Some(path) if !path.is_empty() => Ok(Cost::Timings(PathBuf::from(path))),
+                _ => Err("invalid cost"),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<ColorChoice, &'static str> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("invalid color"),
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{anyhow, Error};
+use std::iter;
+use std::str::{self, FromStr};
+
+#[derive(PartialEq, Eq)]
+pub enum Cfg {
+    Name(String),
+    KeyPair(String, String),
+}
+
+impl FromStr for Cfg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Cfg, Error> {
+        let mut parser = Parser::new(s);
+        let cfg = parser.cfg()?;
+        parser.eof()?;
+        Ok(cfg)
+    }
+}
+
+pub enum CfgExpr {
+    Not(Box<CfgExpr>),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Value(Cfg),
+}
+
+impl CfgExpr {
+    pub fn matches(&self, cfg: &[Cfg]) -> bool {
+        match self {
+            CfgExpr::Not(e) => !e.matches(cfg),
+            CfgExpr::All(e) => e.iter().all(|e| e.matches(cfg)),
+            CfgExpr::Any(e) => e.iter().any(|e| e.matches(cfg)),
+            CfgExpr::Value(e) => cfg.contains(e),
+        }
+    }
+}
+
+impl FromStr for CfgExpr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CfgExpr, Error> {
+        let mut parser = Parser::new(s);
+        let expr = parser.expr()?;
+        parser.eof()?;
+        Ok(expr)
+    }
+}
+
+/// A target triple along with the cfg values rustc reports for it.
+pub struct Target {
+    pub name: String,
+    pub cfg: Vec<Cfg>,
+}
+
+impl Target {
+    /// Determines if a dependency's platform, either a target triple or a `cfg(...)` expression,
+    /// applies to this target.
+    pub fn matches(&self, platform: &str) -> Result<bool, Error> {
+        let platform = platform.trim();
+        if platform.starts_with("cfg(") && platform.ends_with(')') {
+            let expr = platform[4..platform.len() - 1].parse::<CfgExpr>()?;
+            Ok(expr.matches(&self.cfg))
+        } else {
+            Ok(platform == self.name)
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Token<'a> {
+    LeftParen,
+    RightParen,
+    Ident(&'a str),
+    Comma,
+    Equals,
+    String(&'a str),
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    it: iter::Peekable<str::CharIndices<'a>>,
+    peeked: Option<Token<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Parser<'a> {
+        Parser {
+            s,
+            it: s.char_indices().peekable(),
+            peeked: None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<CfgExpr, Error> {
+        let ident = match self.peek()? {
+            Some(&Token::Ident(ident)) => Some(ident),
+            _ => None,
+        };
+
+        match ident {
+            Some(op @ "all") | Some(op @ "any") => {
+                self.next()?;
+                self.expect(Token::LeftParen)?;
+                let mut e = vec![];
+                while self.peek()? != Some(&Token::RightParen) {
+                    e.push(self.expr()?);
+                    if !self.consume(Token::Comma)? {
+                        break;
+                    }
+                }
+                self.expect(Token::RightParen)?;
+                if op == "all" {
+                    Ok(CfgExpr::All(e))
+                } else {
+                    Ok(CfgExpr::Any(e))
+                }
+            }
+            Some("not") => {
+                self.next()?;
+                self.expect(Token::LeftParen)?;
+                let e = self.expr()?;
+                self.expect(Token::RightParen)?;
+                Ok(CfgExpr::Not(Box::new(e)))
+            }
+            _ => self.cfg().map(CfgExpr::Value),
+        }
+    }
+
+    fn cfg(&mut self) -> Result<Cfg, Error> {
+        let name = match self.next()? {
+            Some(Token::Ident(name)) => name.to_string(),
+            _ => return Err(anyhow!("expected identifier in `{}`", self.s)),
+        };
+
+        if !self.consume(Token::Equals)? {
+            return Ok(Cfg::Name(name));
+        }
+
+        match self.next()? {
+            Some(Token::String(value)) => Ok(Cfg::KeyPair(name, value.to_string())),
+            _ => Err(anyhow!("expected string in `{}`", self.s)),
+        }
+    }
+
+    fn eof(&mut self) -> Result<(), Error> {
+        match self.next()? {
+            Some(_) => Err(anyhow!("unexpected content in `{}`", self.s)),
+            None => Ok(()),
+        }
+    }
+
+    fn consume(&mut self, token: Token<'a>) -> Result<bool, Error> {
+        if self.peek()? == Some(&token) {
+            self.next()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expect(&mut self, token: Token<'a>) -> Result<(), Error> {
+        if self.consume(token)? {
+            Ok(())
+        } else {
+            Err(anyhow!("malformed cfg expression `{}`", self.s))
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token<'a>>, Error> {
+        if self.peeked.is_none() {
+            self.peeked = self.token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next(&mut self) -> Result<Option<Token<'a>>, Error> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.token(),
+        }
+    }
+
+    fn token(&mut self) -> Result<Option<Token<'a>>, Error> {
+        while let Some(&(_, ch)) = self.it.peek() {
+            if !ch.is_whitespace() {
+                break;
+            }
+            self.it.next();
+        }
+
+        let (start, ch) = match self.it.next() {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+
+        let token = match ch {
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            '"' => loop {
+                match self.it.next() {
+                    Some((end, '"')) => break Token::String(&self.s[start + 1..end]),
+                    Some(_) => {}
+                    None => return Err(anyhow!("unterminated string in `{}`", self.s)),
+                }
+            },
+            ch if ch.is_alphabetic() || ch == '_' => loop {
+                match self.it.peek() {
+                    Some(&(_, ch)) if ch.is_alphanumeric() || ch == '_' => {
+                        self.it.next();
+                    }
+                    Some(&(end, _)) => break Token::Ident(&self.s[start..end]),
+                    None => break Token::Ident(&self.s[start..]),
+                }
+            },
+            ch => return Err(anyhow!("unexpected character `{}` in `{}`", ch, self.s)),
+        };
+
+        Ok(Some(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> Target {
+        Target {
+            name: "x86_64-unknown-linux-gnu".to_string(),
+            cfg: vec![
+                Cfg::Name("unix".to_string()),
+                Cfg::KeyPair("target_os".to_string(), "linux".to_string()),
+                Cfg::KeyPair("target_pointer_width".to_string(), "64".to_string()),
+            ],
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let cases = [
+            ("x86_64-unknown-linux-gnu", true),
+            ("x86_64-pc-windows-msvc", false),
+            ("cfg(unix)", true),
+            ("cfg(windows)", false),
+            ("cfg(target_os = \"linux\")", true),
+            ("cfg(target_os = \"macos\")", false),
+            ("cfg(all(unix, target_pointer_width = \"64\"))", true),
+            ("cfg(all(unix, windows))", false),
+            ("cfg(any(windows, unix))", true),
+            ("cfg(not(windows))", true),
+            ("cfg(all(unix, ))", true),
+            ("cfg(any(windows,))", false),
+            ("cfg(all())", true),
+            ("cfg(any())", false),
+            ("cfg(not(any()))", true),
+            ("  cfg( not ( unix ) )  ", false),
+        ];
+
+        let target = target();
+        for (platform, expected) in &cases {
+            assert_eq!(target.matches(platform).unwrap(), *expected, "{}", platform);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            "cfg(target_os = \"linux)",
+            "cfg(target_os = )",
+            "cfg(not(unix, windows))",
+            "cfg(all(unix)",
+            "cfg(unix windows)",
+            "cfg(,)",
+            "cfg(1unix)",
+            "cfg()",
+        ];
+
+        let target = target();
+        for platform in &cases {
+            assert!(target.matches(platform).is_err(), "{}", platform);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::args::{Args, Cost};
+use crate::format::Pattern;
+use crate::graph::Graph;
+use crate::size;
+use crate::topo;
+use crate::tree;
+use anyhow::{Context, Error};
+use cargo_metadata::DependencyKind;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct TimingInfo {
+    reason: String,
+    #[serde(default)]
+    package_id: Option<String>,
+    #[serde(default)]
+    duration: f64,
+}
+
+/// Prints the chain of normal and build dependencies from the root package whose packages have
+/// the greatest total cost, which bounds how quickly the root can be built from scratch.
+pub fn print(args: &Args, graph: &Graph) -> Result<(), Error> {
+    let format = Pattern::new(&args.format)?;
+    let root = graph.nodes[tree::find_root(args, graph)?];
+    let costs = costs(&args.cost, graph)?;
+
+    let (path, totals) = critical_path(graph, root, &costs)?;
+
+    let display = |cost: f64| match args.cost {
+        Cost::Timings(_) => format!("{:.2}s", cost),
+        _ => format!("{}", cost),
+    };
+
+    println!(
+        "critical path: {} packages, total cost {}",
+        path.len(),
+        display(totals[&root])
+    );
+    for idx in path {
+        let cost = costs.get(&idx).cloned().unwrap_or(0.);
+        println!(
+            "{:>10} {:>10} {}",
+            display(cost),
+            display(totals[&idx]),
+            format.display(&graph.graph[idx])
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the most costly chain of normal and build dependencies starting at `root`, along with
+/// the cost of the most costly chain starting at each package.
+fn critical_path(
+    graph: &Graph,
+    root: NodeIndex,
+    costs: &HashMap<NodeIndex, f64>,
+) -> Result<(Vec<NodeIndex>, HashMap<NodeIndex, f64>), Error> {
+    // the cost of the most costly chain starting at each package, and the next package on it
+    let mut totals = HashMap::<NodeIndex, f64>::new();
+    let mut next = HashMap::new();
+    for idx in topo::build_order(graph)? {
+        let mut best = None::<(f64, NodeIndex)>;
+        for edge in graph.graph.edges(idx) {
+            if *edge.weight() == DependencyKind::Development {
+                continue;
+            }
+
+            let total = totals[&edge.target()];
+            // break ties consistently
+            let better = match best {
+                None => true,
+                Some((best_total, best_idx)) => {
+                    total > best_total
+                        || (total == best_total
+                            && graph.graph[edge.target()].id < graph.graph[best_idx].id)
+                }
+            };
+            if better {
+                best = Some((total, edge.target()));
+            }
+        }
+
+        let cost = costs.get(&idx).cloned().unwrap_or(0.);
+        totals.insert(idx, cost + best.map_or(0., |(total, _)| total));
+        if let Some((_, idx_next)) = best {
+            next.insert(idx, idx_next);
+        }
+    }
+
+    let mut path = vec![root];
+    while let Some(idx) = next.get(path.last().unwrap()) {
+        path.push(*idx);
+    }
+
+    Ok((path, totals))
+}
+
+fn costs(cost: &Cost, graph: &Graph) -> Result<HashMap<NodeIndex, f64>, Error> {
+    let mut costs = HashMap::new();
+
+    match cost {
+        Cost::Count => {
+            for idx in graph.graph.node_indices() {
+                costs.insert(idx, 1.);
+            }
+        }
+        Cost::Lines => {
+            for idx in graph.graph.node_indices() {
+                let package = &graph.graph[idx];
+                let dir = package.manifest_path.parent().unwrap();
+                // the sources of packages which haven't been downloaded may not exist
+                if dir.is_dir() {
+                    let size = size::measure(dir).with_context(|| {
+                        format!("error measuring sources of `{}`", package.name)
+                    })?;
+                    costs.insert(idx, size.lines as f64);
+                }
+            }
+        }
+        Cost::Timings(path) => {
+            let timings = read_timings(path)
+                .with_context(|| format!("error reading timings from {}", path.display()))?;
+            for (id, idx) in &graph.nodes {
+                if let Some(duration) = timings.get(&id.repr) {
+                    costs.insert(*idx, *duration);
+                }
+            }
+        }
+    }
+
+    Ok(costs)
+}
+
+/// Sums the time spent building each package, including its build script, from the JSON messages
+/// printed by `cargo build --timings=json`.
+fn read_timings(path: &Path) -> Result<HashMap<String, f64>, Error> {
+    Ok(parse_timings(&fs::read_to_string(path)?))
+}
+
+fn parse_timings(contents: &str) -> HashMap<String, f64> {
+    let mut timings = HashMap::new();
+    for line in contents.lines() {
+        // cargo interleaves these with other messages, and possibly non-JSON output
+        let info = match serde_json::from_str::<TimingInfo>(line) {
+            Ok(info) => info,
+            Err(_) => continue,
+        };
+        if info.reason != "timing-info" {
+            continue;
+        }
+
+        if let Some(id) = info.package_id {
+            *timings.entry(id).or_insert(0.) += info.duration;
+        }
+    }
+
+    timings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::{graph, node, package};
+    use cargo_metadata::DependencyKind::{Build, Development, Normal};
+
+    const TIMINGS: &str = r#"{"reason":"compiler-artifact","package_id":"a 1.0.0 (path+file:///ws/a)"}
+{"reason":"timing-info","package_id":"a 1.0.0 (path+file:///ws/a)","target":{"kind":["lib"]},"mode":"build","duration":1.0}
+   Compiling b v1.0.0
+{"reason":"timing-info","package_id":"b 1.0.0 (path+file:///ws/b)","target":{"kind":["custom-build"]},"mode":"run-custom-build","duration":2.0}
+{"reason":"timing-info","package_id":"b 1.0.0 (path+file:///ws/b)","target":{"kind":["lib"]},"mode":"build","duration":3.0}
+{"reason":"timing-info","package_id":"c 1.0.0 (path+file:///ws/c)","target":{"kind":["lib"]},"mode":"build","duration":1.5}
+{"reason":"timing-info","package_id":"d 1.0.0 (path+file:///ws/d)","target":{"kind":["lib"]},"mode":"build","duration":100.0}
+{"reason":"timing-info","package_id":"root 1.0.0 (path+file:///ws/root)","target":{"kind":["lib"]},"mode":"build","duration":0.5}
+{"reason":"build-finished","success":true}
+"#;
+
+    #[test]
+    fn timings() {
+        let timings = parse_timings(TIMINGS);
+        assert_eq!(timings.len(), 5);
+        assert_eq!(timings["a 1.0.0 (path+file:///ws/a)"], 1.0);
+        assert_eq!(timings["b 1.0.0 (path+file:///ws/b)"], 5.0);
+    }
+
+    #[test]
+    fn longest_path() {
+        // the root's tests depend on d, which depends on the root
+        let graph = graph(
+            ["root", "a", "b", "c", "d"]
+                .iter()
+                .map(|name| package(name, serde_json::json!({})))
+                .collect(),
+            &[
+                ("root", "a", Normal),
+                ("root", "b", Build),
+                ("root", "d", Development),
+                ("a", "c", Normal),
+                ("b", "c", Normal),
+                ("d", "root", Normal),
+            ],
+        );
+        let timings = parse_timings(TIMINGS);
+        let costs = graph
+            .nodes
+            .iter()
+            .map(|(id, idx)| (*idx, timings[&id.repr]))
+            .collect();
+
+        let (path, totals) = critical_path(&graph, node(&graph, "root"), &costs).unwrap();
+        let names = path
+            .iter()
+            .map(|idx| &*graph.graph[*idx].name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["root", "b", "c"]);
+        assert_eq!(totals[&node(&graph, "root")], 7.0);
+        assert_eq!(totals[&node(&graph, "a")], 2.5);
+        assert_eq!(totals[&node(&graph, "d")], 107.0);
+    }
+
+    #[test]
+    fn ties() {
+        let graph = graph(
+            ["root", "b", "a"]
+                .iter()
+                .map(|name| package(name, serde_json::json!({})))
+                .collect(),
+            &[("root", "b", Normal), ("root", "a", Normal)],
+        );
+        let costs = graph.graph.node_indices().map(|idx| (idx, 1.)).collect();
+
+        let (path, _) = critical_path(&graph, node(&graph, "root"), &costs).unwrap();
+        assert_eq!(path, [node(&graph, "root"), node(&graph, "a")]);
+    }
+}
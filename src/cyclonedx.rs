@@ -0,0 +1,288 @@
+use crate::args::Args;
+use crate::graph::Graph;
+use crate::lockfile;
+use crate::spdx;
+use crate::spec;
+use crate::tree;
+use anyhow::Error;
+use cargo_metadata::{Package, PackageId};
+use petgraph::graph::NodeIndex;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+const SPEC_VERSION: &str = "1.4";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Bom<'a> {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: Metadata<'a>,
+    components: Vec<Component<'a>>,
+    dependencies: Vec<Dependency<'a>>,
+}
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    tools: Vec<Tool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    component: Option<Component<'a>>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+struct Component<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: &'a str,
+    name: &'a str,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<Hash>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<License>,
+    purl: String,
+}
+
+#[derive(Serialize)]
+struct Hash {
+    alg: &'static str,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct License {
+    expression: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Dependency<'a> {
+    #[serde(rename = "ref")]
+    reference: &'a str,
+    depends_on: Vec<&'a str>,
+}
+
+/// Prints a CycloneDX SBOM of the packages in the graph, either as JSON or XML.
+pub fn print(args: &Args, graph: &Graph, xml: bool) -> Result<(), Error> {
+    let packages = tree::find_exported(args, graph)?;
+    let checksums = lockfile::checksums(graph)?;
+
+    let root = match &args.package {
+        Some(_) => Some(tree::find_root(args, graph)?),
+        None => graph.root.as_ref(),
+    };
+
+    let mut components = vec![];
+    let mut root_component = None;
+    for idx in &packages {
+        let package = &graph.graph[*idx];
+        let component = component(package, &checksums);
+        if root == Some(&package.id) {
+            root_component = Some(component);
+        } else {
+            components.push(component);
+        }
+    }
+
+    let bom = Bom {
+        bom_format: "CycloneDX",
+        spec_version: SPEC_VERSION,
+        version: 1,
+        metadata: Metadata {
+            tools: vec![Tool {
+                name: env!("CARGO_PKG_NAME"),
+                version: env!("CARGO_PKG_VERSION"),
+            }],
+            component: root_component,
+        },
+        components,
+        dependencies: packages.iter().map(|idx| dependency(graph, *idx)).collect(),
+    };
+
+    if xml {
+        print!("{}", to_xml(&bom));
+    } else {
+        println!("{}", serde_json::to_string_pretty(&bom)?);
+    }
+
+    Ok(())
+}
+
+fn component<'a>(package: &'a Package, checksums: &HashMap<&PackageId, String>) -> Component<'a> {
+    let is_bin = package
+        .targets
+        .iter()
+        .any(|t| t.kind.iter().any(|k| k == "bin"));
+
+    Component {
+        kind: if is_bin { "application" } else { "library" },
+        bom_ref: &package.id.repr,
+        name: &package.name,
+        version: package.version.to_string(),
+        description: package.description.as_deref(),
+        hashes: checksums
+            .get(&package.id)
+            .map(|checksum| Hash {
+                alg: "SHA-256",
+                content: checksum.clone(),
+            })
+            .into_iter()
+            .collect(),
+        licenses: package
+            .license
+            .as_deref()
+            .map(|license| License {
+                expression: spdx::license_expression(license),
+            })
+            .into_iter()
+            .collect(),
+        purl: spec::purl(package),
+    }
+}
+
+fn dependency(graph: &Graph, idx: NodeIndex) -> Dependency<'_> {
+    // a package may depend on another in several ways, like both normally and as a build dependency
+    let depends_on = graph
+        .graph
+        .neighbors(idx)
+        .map(|idx| &*graph.graph[idx].id.repr)
+        .collect::<BTreeSet<_>>();
+
+    Dependency {
+        reference: &graph.graph[idx].id.repr,
+        depends_on: depends_on.into_iter().collect(),
+    }
+}
+
+fn to_xml(bom: &Bom<'_>) -> String {
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        out,
+        r#"<bom xmlns="http://cyclonedx.org/schema/bom/{}" version="{}">"#,
+        bom.spec_version, bom.version
+    )
+    .unwrap();
+
+    writeln!(out, "  <metadata>").unwrap();
+    writeln!(out, "    <tools>").unwrap();
+    for tool in &bom.metadata.tools {
+        writeln!(out, "      <tool>").unwrap();
+        writeln!(out, "        <name>{}</name>", escape(tool.name)).unwrap();
+        writeln!(out, "        <version>{}</version>", escape(tool.version)).unwrap();
+        writeln!(out, "      </tool>").unwrap();
+    }
+    writeln!(out, "    </tools>").unwrap();
+    if let Some(component) = &bom.metadata.component {
+        write_component(&mut out, component, "    ");
+    }
+    writeln!(out, "  </metadata>").unwrap();
+
+    writeln!(out, "  <components>").unwrap();
+    for component in &bom.components {
+        write_component(&mut out, component, "    ");
+    }
+    writeln!(out, "  </components>").unwrap();
+
+    writeln!(out, "  <dependencies>").unwrap();
+    for dependency in &bom.dependencies {
+        let reference = escape(dependency.reference);
+        if dependency.depends_on.is_empty() {
+            writeln!(out, r#"    <dependency ref="{}"/>"#, reference).unwrap();
+            continue;
+        }
+
+        writeln!(out, r#"    <dependency ref="{}">"#, reference).unwrap();
+        for depends_on in &dependency.depends_on {
+            writeln!(out, r#"      <dependency ref="{}"/>"#, escape(depends_on)).unwrap();
+        }
+        writeln!(out, "    </dependency>").unwrap();
+    }
+    writeln!(out, "  </dependencies>").unwrap();
+
+    writeln!(out, "</bom>").unwrap();
+    out
+}
+
+fn write_component(out: &mut String, component: &Component<'_>, indent: &str) {
+    writeln!(
+        out,
+        r#"{}<component type="{}" bom-ref="{}">"#,
+        indent,
+        component.kind,
+        escape(component.bom_ref)
+    )
+    .unwrap();
+    writeln!(out, "{}  <name>{}</name>", indent, escape(component.name)).unwrap();
+    writeln!(
+        out,
+        "{}  <version>{}</version>",
+        indent,
+        escape(&component.version)
+    )
+    .unwrap();
+    if let Some(description) = component.description {
+        writeln!(
+            out,
+            "{}  <description>{}</description>",
+            indent,
+            escape(description)
+        )
+        .unwrap();
+    }
+    if !component.hashes.is_empty() {
+        writeln!(out, "{}  <hashes>", indent).unwrap();
+        for hash in &component.hashes {
+            writeln!(
+                out,
+                r#"{}    <hash alg="{}">{}</hash>"#,
+                indent,
+                hash.alg,
+                escape(&hash.content)
+            )
+            .unwrap();
+        }
+        writeln!(out, "{}  </hashes>", indent).unwrap();
+    }
+    if !component.licenses.is_empty() {
+        writeln!(out, "{}  <licenses>", indent).unwrap();
+        for license in &component.licenses {
+            writeln!(
+                out,
+                "{}    <expression>{}</expression>",
+                indent,
+                escape(&license.expression)
+            )
+            .unwrap();
+        }
+        writeln!(out, "{}  </licenses>", indent).unwrap();
+    }
+    writeln!(out, "{}  <purl>{}</purl>", indent, escape(&component.purl)).unwrap();
+    writeln!(out, "{}</component>", indent).unwrap();
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
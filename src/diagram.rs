@@ -0,0 +1,119 @@
+use crate::args::Args;
+use crate::format::Pattern;
+use crate::graph::Graph;
+use crate::tree;
+use anyhow::Error;
+use cargo_metadata::DependencyKind;
+use petgraph::visit::EdgeRef;
+use petgraph::EdgeDirection;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Clone, Copy)]
+pub enum Diagram {
+    Mermaid,
+    PlantUml,
+}
+
+/// Prints the tree rooted at the selected package as a diagram.
+///
+/// Edges always point from a package to its dependency, even when the tree is inverted.
+pub fn print(args: &Args, graph: &Graph, diagram: Diagram) -> Result<(), Error> {
+    let format = Pattern::new(&args.format)?;
+    let root = graph.nodes[tree::find_root(args, graph)?];
+    let direction = if args.invert {
+        EdgeDirection::Incoming
+    } else {
+        EdgeDirection::Outgoing
+    };
+
+    let mut nodes = vec![root];
+    let mut depths = HashMap::new();
+    let mut edges = vec![];
+    let mut seen_edges = HashSet::new();
+    let mut queue = VecDeque::new();
+    depths.insert(root, 0);
+    queue.push_back(root);
+
+    while let Some(idx) = queue.pop_front() {
+        let depth = depths[&idx];
+        if args.depth.is_some_and(|max| depth >= max) {
+            continue;
+        }
+
+        let mut neighbors = graph
+            .graph
+            .edges_directed(idx, direction)
+            .map(|edge| match direction {
+                EdgeDirection::Incoming => (edge.source(), *edge.weight()),
+                EdgeDirection::Outgoing => (edge.target(), *edge.weight()),
+            })
+            .collect::<Vec<_>>();
+        // ensure a consistent output ordering
+        neighbors.sort_by_key(|(idx, _)| &graph.graph[*idx].id);
+
+        for (neighbor, kind) in neighbors {
+            let edge = match direction {
+                EdgeDirection::Incoming => (neighbor, idx),
+                EdgeDirection::Outgoing => (idx, neighbor),
+            };
+            let edge = (edge.0, edge.1, kind_label(kind));
+            if seen_edges.insert(edge) {
+                edges.push(edge);
+            }
+
+            if let Entry::Vacant(entry) = depths.entry(neighbor) {
+                entry.insert(depth + 1);
+                nodes.push(neighbor);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    let ids = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, idx)| (*idx, format!("n{}", i)))
+        .collect::<HashMap<_, _>>();
+
+    match diagram {
+        Diagram::Mermaid => {
+            // keep the root at the top when edges point towards it
+            println!("graph {}", if args.invert { "BT" } else { "TD" });
+            for (i, idx) in nodes.iter().enumerate() {
+                let label = format.display(&graph.graph[*idx]).to_string();
+                println!("    n{}[\"{}\"]", i, label.replace('"', "#quot;"));
+            }
+            for (from, to, kind) in &edges {
+                match kind {
+                    Some(kind) => println!("    {} -.->|{}| {}", ids[from], kind, ids[to]),
+                    None => println!("    {} --> {}", ids[from], ids[to]),
+                }
+            }
+        }
+        Diagram::PlantUml => {
+            println!("@startuml");
+            for (i, idx) in nodes.iter().enumerate() {
+                let label = format.display(&graph.graph[*idx]).to_string();
+                println!("component \"{}\" as n{}", label.replace('"', "'"), i);
+            }
+            for (from, to, kind) in &edges {
+                match kind {
+                    Some(kind) => println!("{} ..> {} : {}", ids[from], ids[to], kind),
+                    None => println!("{} --> {}", ids[from], ids[to]),
+                }
+            }
+            println!("@enduml");
+        }
+    }
+
+    Ok(())
+}
+
+fn kind_label(kind: DependencyKind) -> Option<&'static str> {
+    match kind {
+        DependencyKind::Build => Some("build"),
+        DependencyKind::Development => Some("dev"),
+        _ => None,
+    }
+}
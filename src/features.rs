@@ -0,0 +1,495 @@
+use crate::args::Args;
+use crate::cfg::Target;
+use crate::graph::{Graph, Platform};
+use cargo_metadata::{Dependency, DependencyKind, Package};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// The features enabled for each package, separately for its host and target builds.
+pub type Features = HashMap<(NodeIndex, Platform), BTreeSet<String>>;
+
+/// Resolves the features of each package the way resolver 2 does, keeping the features of
+/// packages built for the host, i.e. build dependencies, proc-macros and their dependencies, apart
+/// from those of packages built for the target.
+///
+/// Dependencies are taken from the edges of the graph, so it must not have been split yet.
+/// Dependencies declared for specific platforms only apply if they match one of `targets`, like
+/// the edges of the graph, or always if it was resolved for all targets.
+pub fn resolve(args: &Args, graph: &Graph, targets: &[Target]) -> Features {
+    let mut resolver = Resolver {
+        graph,
+        targets,
+        features: HashMap::new(),
+        queue: vec![],
+    };
+
+    let roots = match &graph.root {
+        Some(root) => vec![root],
+        None => graph.workspace_members.iter().collect(),
+    };
+    for root in roots {
+        let idx = match graph.nodes.get(root) {
+            Some(idx) => *idx,
+            None => continue,
+        };
+        let package = &graph.graph[idx];
+
+        let mut features = vec![];
+        if args.all_features {
+            features.extend(package.features.keys().cloned());
+            features.extend(optional_dependencies(package).map(str::to_string));
+        } else {
+            for feature in args
+                .features
+                .iter()
+                .flat_map(|f| f.split([' ', ',']))
+                .filter(|f| !f.is_empty())
+            {
+                match feature.split_once('/') {
+                    Some((name, feature)) if name == package.name => {
+                        features.push(feature.to_string())
+                    }
+                    Some(_) => {}
+                    None => features.push(feature.to_string()),
+                }
+            }
+        }
+        if !args.no_default_features {
+            features.push("default".to_string());
+        }
+
+        // proc-macros are built for the host even when they're built on their own
+        let platform = dep_platform(DependencyKind::Normal, package, Platform::Target);
+        resolver.activate(idx, platform, features);
+    }
+
+    while let Some((idx, platform)) = resolver.queue.pop() {
+        resolver.process(idx, platform);
+    }
+
+    resolver.features
+}
+
+struct Resolver<'a> {
+    graph: &'a Graph,
+    targets: &'a [Target],
+    features: Features,
+    queue: Vec<(NodeIndex, Platform)>,
+}
+
+impl Resolver<'_> {
+    /// Enables features of a package, queueing it to have its dependencies activated if anything
+    /// changed.
+    fn activate(&mut self, idx: NodeIndex, platform: Platform, features: Vec<String>) {
+        let package = &self.graph.graph[idx];
+        // features which don't exist, like `default` for most packages, are ignored
+        let features = features.into_iter().filter(|f| {
+            package.features.contains_key(f) || optional_dependencies(package).any(|d| d == f)
+        });
+
+        let changed = match self.features.entry((idx, platform)) {
+            Entry::Vacant(entry) => {
+                entry.insert(features.collect());
+                true
+            }
+            Entry::Occupied(mut entry) => {
+                let mut changed = false;
+                for feature in features {
+                    changed |= entry.get_mut().insert(feature);
+                }
+                changed
+            }
+        };
+        if changed {
+            self.queue.push((idx, platform));
+        }
+    }
+
+    fn process(&mut self, idx: NodeIndex, platform: Platform) {
+        let package = &self.graph.graph[idx];
+
+        // expand features enabling other features, and collect what they enable in dependencies
+        let mut enabled = self.features[&(idx, platform)].clone();
+        let mut pending = enabled.iter().cloned().collect::<Vec<_>>();
+        let mut enabled_deps = HashSet::new();
+        let mut dep_features = HashMap::<&str, Vec<String>>::new();
+        while let Some(feature) = pending.pop() {
+            let values = match package.features.get(&feature) {
+                Some(values) => values,
+                // an optional dependency's implicit feature
+                None => {
+                    enabled_deps.insert(feature);
+                    continue;
+                }
+            };
+
+            for value in values {
+                if let Some(dep) = value.strip_prefix("dep:") {
+                    enabled_deps.insert(dep.to_string());
+                } else if let Some((dep, feature)) = value.split_once('/') {
+                    // weak features don't enable the dependency itself
+                    let dep = match dep.strip_suffix('?') {
+                        Some(dep) => dep,
+                        None => {
+                            enabled_deps.insert(dep.to_string());
+                            dep
+                        }
+                    };
+                    dep_features
+                        .entry(dep)
+                        .or_default()
+                        .push(feature.to_string());
+                } else if enabled.insert(value.clone()) {
+                    pending.push(value.clone());
+                }
+            }
+        }
+        self.features.insert((idx, platform), enabled);
+
+        let mut activations = vec![];
+        for edge in self.graph.graph.edges(idx) {
+            let dep = &self.graph.graph[edge.target()];
+            let dep_platform = dep_platform(*edge.weight(), dep, platform);
+
+            for decl in declarations(package, *edge.weight(), dep) {
+                if !self.applies(decl) {
+                    continue;
+                }
+
+                let name = decl.rename.as_ref().unwrap_or(&decl.name);
+                if decl.optional && !enabled_deps.contains(name) {
+                    continue;
+                }
+
+                let mut features = decl.features.clone();
+                if decl.uses_default_features {
+                    features.push("default".to_string());
+                }
+                features.extend(dep_features.get(&**name).into_iter().flatten().cloned());
+                activations.push((edge.target(), dep_platform, features));
+            }
+        }
+
+        for (dep, dep_platform, features) in activations {
+            self.activate(dep, dep_platform, features);
+        }
+    }
+
+    /// Determines if a declaration applies to the targets being resolved for.
+    fn applies(&self, decl: &Dependency) -> bool {
+        match &decl.target {
+            Some(platform) if !self.targets.is_empty() => self
+                .targets
+                .iter()
+                // invalid platforms were already reported when building the graph
+                .any(|target| target.matches(&platform.repr).unwrap_or(false)),
+            _ => true,
+        }
+    }
+}
+
+/// Returns whether a dependency of a package built for `platform` is built for the host or the
+/// target.
+pub fn dep_platform(kind: DependencyKind, dep: &Package, platform: Platform) -> Platform {
+    let proc_macro = dep
+        .targets
+        .iter()
+        .any(|t| t.kind.iter().any(|k| k == "proc-macro"));
+    if kind == DependencyKind::Build || proc_macro {
+        Platform::Host
+    } else {
+        platform
+    }
+}
+
+/// Returns the declarations in a package's manifest which resolved to a dependency of some kind.
+fn declarations<'a>(
+    package: &'a Package,
+    kind: DependencyKind,
+    dep: &'a Package,
+) -> impl Iterator<Item = &'a Dependency> {
+    let candidates = package
+        .dependencies
+        .iter()
+        .filter(move |d| d.name == dep.name && d.kind == kind)
+        .collect::<Vec<_>>();
+    // a package can depend on several versions of another, but version requirements don't match
+    // prereleases, so only use them to choose between declarations
+    let matching = candidates
+        .iter()
+        .filter(|d| d.req.matches(&dep.version))
+        .cloned()
+        .collect::<Vec<_>>();
+    if matching.is_empty() {
+        candidates.into_iter()
+    } else {
+        matching.into_iter()
+    }
+}
+
+fn optional_dependencies(package: &Package) -> impl Iterator<Item = &str> {
+    package
+        .dependencies
+        .iter()
+        .filter(|d| d.optional)
+        .map(|d| d.rename.as_deref().unwrap_or(&d.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::{graph, node, package};
+    use cargo_metadata::DependencyKind::{Build, Development, Normal};
+    use serde_json::{json, Value};
+    use structopt::StructOpt;
+
+    /// A dependency declaration in a manifest, with `fields` overriding the defaults.
+    fn dep(name: &str, kind: DependencyKind, fields: Value) -> Value {
+        let mut dep = json!({
+            "name": name,
+            "source": null,
+            "req": "^1.0",
+            "kind": kind,
+            "optional": false,
+            "uses_default_features": true,
+            "features": [],
+            "target": null,
+            "rename": null,
+        });
+        for (key, value) in fields.as_object().unwrap() {
+            dep[key] = value.clone();
+        }
+        dep
+    }
+
+    /// Returns the features of a package's build for a platform, if it's built for it at all.
+    fn features(
+        features: &Features,
+        graph: &Graph,
+        name: &str,
+        platform: Platform,
+    ) -> Option<Vec<String>> {
+        features
+            .get(&(node(graph, name), platform))
+            .map(|features| features.iter().cloned().collect())
+    }
+
+    fn args(args: &[&str]) -> Args {
+        Args::from_iter(["tree"].iter().chain(args))
+    }
+
+    fn lib_features() -> Value {
+        json!({ "features": { "std": [], "build": [], "pm": [], "test": [] } })
+    }
+
+    #[test]
+    fn host_and_target() {
+        let graph = graph(
+            vec![
+                package(
+                    "root",
+                    json!({
+                        "dependencies": [
+                            dep("lib", Normal, json!({ "features": ["std"] })),
+                            dep("lib", Build, json!({ "features": ["build"] })),
+                            dep("pm", Normal, json!({})),
+                        ],
+                    }),
+                ),
+                package("lib", lib_features()),
+                package(
+                    "pm",
+                    json!({
+                        "targets": [{
+                            "name": "pm",
+                            "kind": ["proc-macro"],
+                            "src_path": "/ws/pm/src/lib.rs",
+                        }],
+                        "dependencies": [dep("lib", Normal, json!({ "features": ["pm"] }))],
+                    }),
+                ),
+            ],
+            &[
+                ("root", "lib", Normal),
+                ("root", "lib", Build),
+                ("root", "pm", Normal),
+                ("pm", "lib", Normal),
+            ],
+        );
+
+        let resolved = resolve(&args(&[]), &graph, &[]);
+        assert_eq!(
+            features(&resolved, &graph, "lib", Platform::Target),
+            Some(vec!["std".to_string()])
+        );
+        assert_eq!(
+            features(&resolved, &graph, "lib", Platform::Host),
+            Some(vec!["build".to_string(), "pm".to_string()])
+        );
+        assert_eq!(
+            features(&resolved, &graph, "pm", Platform::Host),
+            Some(vec![])
+        );
+        assert_eq!(features(&resolved, &graph, "pm", Platform::Target), None);
+        assert_eq!(features(&resolved, &graph, "root", Platform::Host), None);
+    }
+
+    #[test]
+    fn dev_dependencies() {
+        let graph = graph(
+            vec![
+                package(
+                    "root",
+                    json!({
+                        "dependencies": [
+                            dep("lib", Normal, json!({ "features": ["std"] })),
+                            dep("lib", Development, json!({ "features": ["test"] })),
+                        ],
+                    }),
+                ),
+                package("lib", lib_features()),
+            ],
+            &[("root", "lib", Normal), ("root", "lib", Development)],
+        );
+
+        let resolved = resolve(&args(&[]), &graph, &[]);
+        assert_eq!(
+            features(&resolved, &graph, "lib", Platform::Target),
+            Some(vec!["std".to_string(), "test".to_string()])
+        );
+        assert_eq!(features(&resolved, &graph, "lib", Platform::Host), None);
+    }
+
+    #[test]
+    fn feature_syntax() {
+        let graph = graph(
+            vec![
+                package(
+                    "root",
+                    json!({
+                        "features": {
+                            "default": ["json"],
+                            "json": ["dep:json", "serde?/derive"],
+                            "full": ["serde", "serde?/rc", "tls"],
+                            "tls": ["net/rustls"],
+                        },
+                        "dependencies": [
+                            dep("json", Normal, json!({ "optional": true })),
+                            dep("serde", Normal, json!({ "optional": true })),
+                            dep("net", Normal, json!({ "uses_default_features": false })),
+                        ],
+                    }),
+                ),
+                package("json", json!({})),
+                package(
+                    "serde",
+                    json!({ "features": { "default": ["std"], "std": [], "derive": [], "rc": [] } }),
+                ),
+                package(
+                    "net",
+                    json!({ "features": { "default": ["native"], "native": [], "rustls": [] } }),
+                ),
+            ],
+            &[
+                ("root", "json", Normal),
+                ("root", "serde", Normal),
+                ("root", "net", Normal),
+            ],
+        );
+
+        // weak features don't enable the optional dependency
+        let resolved = resolve(&args(&[]), &graph, &[]);
+        assert_eq!(
+            features(&resolved, &graph, "root", Platform::Target),
+            Some(vec!["default".to_string(), "json".to_string()])
+        );
+        assert_eq!(
+            features(&resolved, &graph, "json", Platform::Target),
+            Some(vec![])
+        );
+        assert_eq!(features(&resolved, &graph, "serde", Platform::Target), None);
+        assert_eq!(
+            features(&resolved, &graph, "net", Platform::Target),
+            Some(vec![])
+        );
+
+        // but do add features to it once it's enabled
+        let resolved = resolve(
+            &args(&["--features", "full", "--no-default-features"]),
+            &graph,
+            &[],
+        );
+        assert_eq!(features(&resolved, &graph, "json", Platform::Target), None);
+        assert_eq!(
+            features(&resolved, &graph, "serde", Platform::Target),
+            Some(vec![
+                "default".to_string(),
+                "rc".to_string(),
+                "std".to_string()
+            ])
+        );
+        assert_eq!(
+            features(&resolved, &graph, "net", Platform::Target),
+            Some(vec!["rustls".to_string()])
+        );
+
+        let resolved = resolve(&args(&["--features", "root/full"]), &graph, &[]);
+        assert_eq!(
+            features(&resolved, &graph, "serde", Platform::Target),
+            Some(vec![
+                "default".to_string(),
+                "derive".to_string(),
+                "rc".to_string(),
+                "std".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn target_specific() {
+        let graph = graph(
+            vec![
+                package(
+                    "root",
+                    json!({
+                        "dependencies": [
+                            dep(
+                                "lib",
+                                Normal,
+                                json!({ "features": ["std"], "target": "cfg(unix)" }),
+                            ),
+                            dep(
+                                "lib",
+                                Normal,
+                                json!({ "features": ["test"], "target": "cfg(windows)" }),
+                            ),
+                        ],
+                    }),
+                ),
+                package("lib", lib_features()),
+            ],
+            &[("root", "lib", Normal)],
+        );
+
+        let linux = Target {
+            name: "x86_64-unknown-linux-gnu".to_string(),
+            cfg: vec![
+                "unix".parse().unwrap(),
+                "target_os=\"linux\"".parse().unwrap(),
+            ],
+        };
+        let resolved = resolve(&args(&[]), &graph, &[linux]);
+        assert_eq!(
+            features(&resolved, &graph, "lib", Platform::Target),
+            Some(vec!["std".to_string()])
+        );
+
+        let resolved = resolve(&args(&[]), &graph, &[]);
+        assert_eq!(
+            features(&resolved, &graph, "lib", Platform::Target),
+            Some(vec!["std".to_string(), "test".to_string()])
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::format::parse::{Parser, RawChunk};
+use crate::spec;
 use anyhow::{anyhow, Error};
 use cargo_metadata::Package;
 use std::fmt;
@@ -10,6 +11,7 @@ enum Chunk {
     Package,
     License,
     Repository,
+    Purl,
 }
 
 pub struct Pattern(Vec<Chunk>);
@@ -24,6 +26,7 @@ impl Pattern {
                 RawChunk::Argument("p") => Chunk::Package,
                 RawChunk::Argument("l") => Chunk::License,
                 RawChunk::Argument("r") => Chunk::Repository,
+                RawChunk::Argument("purl") => Chunk::Purl,
                 RawChunk::Argument(ref a) => {
                     return Err(anyhow!("unsupported pattern `{}`", a));
                 }
@@ -77,9 +80,68 @@ impl<'a> fmt::Display for Display<'a> {
                         write!(fmt, "{}", repository)?
                     }
                 }
+                Chunk::Purl => fmt.write_str(&spec::purl(self.package))?,
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::package;
+    use serde_json::json;
+
+    #[test]
+    fn purls() {
+        let cases = [
+            (
+                package(
+                    "serde",
+                    json!({
+                        "version": "1.0.100",
+                        "source": "registry+https://github.com/rust-lang/crates.io-index",
+                    }),
+                ),
+                "pkg:cargo/serde@1.0.100",
+            ),
+            (
+                package(
+                    "foo",
+                    json!({
+                        "version": "0.2.0+meta",
+                        "source": "git+https://example.com/foo#abc",
+                    }),
+                ),
+                "pkg:cargo/foo@0.2.0%2Bmeta?vcs_url=git%2Bhttps%3A%2F%2Fexample.com%2Ffoo%23abc",
+            ),
+            (
+                package(
+                    "bar",
+                    json!({
+                        "version": "0.1.0",
+                        "source": "registry+https://example.com/index",
+                    }),
+                ),
+                "pkg:cargo/bar@0.1.0?repository_url=https%3A%2F%2Fexample.com%2Findex",
+            ),
+            (
+                package("baz", json!({ "version": "0.1.0" })),
+                "pkg:cargo/baz@0.1.0",
+            ),
+        ];
+
+        let pattern = Pattern::new("{purl}").unwrap();
+        for (package, expected) in &cases {
+            assert_eq!(pattern.display(package).to_string(), *expected);
+        }
+
+        let pattern = Pattern::new("{p} <{purl}>").unwrap();
+        assert_eq!(
+            pattern.display(&cases[0].0).to_string(),
+            "serde v1.0.100 <pkg:cargo/serde@1.0.100>"
+        );
+    }
+}
@@ -1,54 +1,257 @@
 use crate::args::Args;
-use anyhow::{anyhow, Error};
+use crate::cfg::Target;
+use crate::features::{self, Features};
+use anyhow::{anyhow, Context, Error};
 use cargo_metadata::{DependencyKind, Metadata, Package, PackageId};
 use petgraph::graph::NodeIndex;
 use petgraph::stable_graph::StableGraph;
-use petgraph::visit::Dfs;
-use std::collections::HashMap;
+use petgraph::visit::{Dfs, EdgeRef, IntoEdgeReferences};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// The reason a package in the lockfile was left out of the graph.
+pub enum Exclusion {
+    /// The package is only used by targets other than the requested ones.
+    Target,
+    /// The package is only used as a dev-dependency, which were skipped.
+    DevDependency,
+    /// The package isn't a dependency of the root package.
+    Unreachable,
+}
+
+/// Whether a package is built for the host, to run during the build, or for the target.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Platform {
+    Host,
+    Target,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Host => fmt.write_str("host"),
+            Platform::Target => fmt.write_str("target"),
+        }
+    }
+}
 
 pub struct Graph {
     pub graph: StableGraph<Package, DependencyKind>,
     pub nodes: HashMap<PackageId, NodeIndex>,
     pub root: Option<PackageId>,
+    pub workspace_members: Vec<PackageId>,
+    /// The targets which pull in each package, in the order they were requested.
+    ///
+    /// This is empty if the graph was built for all targets.
+    pub targets: HashMap<PackageId, Vec<String>>,
+    /// The number of targets the graph was built for.
+    pub target_count: usize,
+    pub workspace_root: PathBuf,
+    /// The nodes of the host builds of packages built separately for the host and the target,
+    /// whose target builds are in `nodes`.
+    ///
+    /// This is only populated by `split_platforms`.
+    pub host_nodes: HashMap<PackageId, NodeIndex>,
+    /// What the graph was built from, kept to explain why packages are missing from it.
+    inputs: Option<Inputs>,
+}
+
+struct Inputs {
+    metadata: Metadata,
+    targets: Vec<Target>,
+    no_dev_dependencies: bool,
+}
+
+impl Graph {
+    /// Returns the targets which pull in a package if it isn't pulled in by all of them.
+    pub fn partial_targets(&self, id: &PackageId) -> Option<&[String]> {
+        match self.targets.get(id) {
+            Some(targets) if targets.len() < self.target_count => Some(targets),
+            _ => None,
+        }
+    }
+
+    /// Returns the platform a node is built for, if its package is built separately for the host
+    /// and the target.
+    pub fn platform(&self, idx: NodeIndex) -> Option<Platform> {
+        let id = &self.graph[idx].id;
+        match self.host_nodes.get(id) {
+            Some(host) if *host == idx => Some(Platform::Host),
+            Some(_) => Some(Platform::Target),
+            None => None,
+        }
+    }
+
+    /// Returns the nodes of a package, which has one for the host as well if it's built
+    /// separately for the host and the target.
+    pub fn package_nodes(&self, id: &PackageId) -> Vec<NodeIndex> {
+        let mut nodes = vec![self.nodes[id]];
+        nodes.extend(self.host_nodes.get(id).cloned());
+        nodes
+    }
+
+    /// Returns the packages in the lockfile which were left out of the graph, and why.
+    ///
+    /// This builds the graph again with its filters relaxed, so it's only meant for explaining
+    /// errors.
+    pub fn excluded(&self) -> Result<Vec<(&Package, Exclusion)>, Error> {
+        match &self.inputs {
+            Some(inputs) => find_excluded(self, inputs),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Returns the name of a kind of dependency as used in machine-readable output.
+pub fn kind_name(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Build => "build",
+        DependencyKind::Development => "dev",
+        _ => "normal",
+    }
+}
+
+pub fn build(args: &Args, metadata: Metadata, targets: Vec<Target>) -> Result<Graph, Error> {
+    let mut graph = Graph {
+        graph: StableGraph::new(),
+        nodes: HashMap::new(),
+        root: None,
+        workspace_members: metadata.workspace_members.clone(),
+        targets: HashMap::new(),
+        target_count: 0,
+        inputs: None,
+        workspace_root: metadata.workspace_root.clone(),
+        host_nodes: HashMap::new(),
+    };
+
+    let no_dev_dependencies = args.no_dev_dependencies;
+    if targets.is_empty() {
+        let target_graph = build_target(&metadata, None, no_dev_dependencies)?;
+        merge(&mut graph, target_graph, None);
+    } else {
+        for target in &targets {
+            let target_graph = build_target(&metadata, Some(target), no_dev_dependencies)?;
+            merge(&mut graph, target_graph, Some(&target.name));
+        }
+    }
+
+    graph.inputs = Some(Inputs {
+        metadata,
+        targets,
+        no_dev_dependencies,
+    });
+
+    Ok(graph)
 }
 
-pub fn build(args: &Args, metadata: Metadata) -> Result<Graph, Error> {
-    let resolve = metadata.resolve.unwrap();
+/// Determines why packages were left out of the graph by relaxing its filters one at a time.
+fn find_excluded<'a>(
+    graph: &Graph,
+    inputs: &'a Inputs,
+) -> Result<Vec<(&'a Package, Exclusion)>, Error> {
+    let metadata = &inputs.metadata;
+    let targets = &inputs.targets;
+    let no_dev_dependencies = inputs.no_dev_dependencies;
+
+    if metadata
+        .packages
+        .iter()
+        .all(|p| graph.nodes.contains_key(&p.id))
+    {
+        return Ok(vec![]);
+    }
+
+    let all_targets = build_target(metadata, None, no_dev_dependencies)?.nodes;
+
+    let mut dev_dependencies = HashSet::new();
+    if no_dev_dependencies {
+        if targets.is_empty() {
+            dev_dependencies.extend(build_target(metadata, None, false)?.nodes.into_keys());
+        }
+        for target in targets {
+            dev_dependencies.extend(
+                build_target(metadata, Some(target), false)?
+                    .nodes
+                    .into_keys(),
+            );
+        }
+    }
+
+    let mut excluded = vec![];
+    for package in &metadata.packages {
+        if graph.nodes.contains_key(&package.id) {
+            continue;
+        }
+
+        let exclusion = if all_targets.contains_key(&package.id) {
+            Exclusion::Target
+        } else if dev_dependencies.contains(&package.id) {
+            Exclusion::DevDependency
+        } else {
+            Exclusion::Unreachable
+        };
+        excluded.push((package, exclusion));
+    }
+
+    Ok(excluded)
+}
+
+fn build_target(
+    metadata: &Metadata,
+    target: Option<&Target>,
+    no_dev_dependencies: bool,
+) -> Result<Graph, Error> {
+    let resolve = metadata.resolve.as_ref().unwrap();
 
     let mut graph = Graph {
         graph: StableGraph::new(),
         nodes: HashMap::new(),
-        root: resolve.root,
+        root: resolve.root.clone(),
+        workspace_members: vec![],
+        targets: HashMap::new(),
+        target_count: 0,
+        inputs: None,
+        workspace_root: metadata.workspace_root.clone(),
+        host_nodes: HashMap::new(),
     };
 
-    for package in metadata.packages {
+    for package in &metadata.packages {
         let id = package.id.clone();
-        let index = graph.graph.add_node(package);
+        let index = graph.graph.add_node(package.clone());
         graph.nodes.insert(id, index);
     }
 
-    for node in resolve.nodes {
+    for node in &resolve.nodes {
         if node.deps.len() != node.dependencies.len() {
             return Err(anyhow!("cargo tree requires cargo 1.41 or newer"));
         }
 
         let from = graph.nodes[&node.id];
-        for dep in node.deps {
+        for dep in &node.deps {
             if dep.dep_kinds.is_empty() {
                 return Err(anyhow!("cargo tree requires cargo 1.41 or newer"));
             }
 
             // https://github.com/rust-lang/cargo/issues/7752
             let mut kinds = vec![];
-            for kind in dep.dep_kinds {
-                if !kinds.iter().any(|k| *k == kind.kind) {
+            for kind in &dep.dep_kinds {
+                if let (Some(target), Some(platform)) = (target, &kind.target) {
+                    if !target.matches(&platform.repr)? {
+                        continue;
+                    }
+                }
+
+                if !kinds.contains(&kind.kind) {
                     kinds.push(kind.kind);
                 }
             }
 
             let to = graph.nodes[&dep.pkg];
             for kind in kinds {
-                if args.no_dev_dependencies && kind == DependencyKind::Development {
+                if no_dev_dependencies && kind == DependencyKind::Development {
                     continue;
                 }
 
@@ -57,21 +260,357 @@ pub fn build(args: &Args, metadata: Metadata) -> Result<Graph, Error> {
         }
     }
 
-    // prune nodes not reachable from the root package (directionally)
-    if let Some(root) = &graph.root {
-        let mut dfs = Dfs::new(&graph.graph, graph.nodes[root]);
+    // prune nodes not reachable from the root package, or from the workspace members in a
+    // virtual workspace (directionally)
+    let roots = match &graph.root {
+        Some(root) => vec![root],
+        None => metadata.workspace_members.iter().collect(),
+    };
+    let mut dfs = Dfs::empty(&graph.graph);
+    for root in roots {
+        dfs.move_to(graph.nodes[root]);
         while dfs.next(&graph.graph).is_some() {}
+    }
+
+    let g = &mut graph.graph;
+    graph.nodes.retain(|_, idx| {
+        if !dfs.discovered.contains(idx.index()) {
+            g.remove_node(*idx);
+            false
+        } else {
+            true
+        }
+    });
+
+    Ok(graph)
+}
+
+/// Merges the graph resolved for a single target into the combined graph.
+fn merge(graph: &mut Graph, mut other: Graph, target: Option<&str>) {
+    graph.root = other.root.take();
+
+    let mut edges = vec![];
+    for edge in (&other.graph).edge_references() {
+        let from = other.graph[edge.source()].id.clone();
+        let to = other.graph[edge.target()].id.clone();
+        edges.push((from, to, *edge.weight()));
+    }
+
+    for (id, idx) in other.nodes {
+        let package = other.graph.remove_node(idx).unwrap();
+        if let Some(target) = target {
+            graph
+                .targets
+                .entry(id.clone())
+                .or_default()
+                .push(target.to_string());
+        }
 
         let g = &mut graph.graph;
-        graph.nodes.retain(|_, idx| {
-            if !dfs.discovered.contains(idx.index()) {
-                g.remove_node(*idx);
-                false
-            } else {
-                true
+        graph.nodes.entry(id).or_insert_with(|| g.add_node(package));
+    }
+
+    for (from, to, kind) in edges {
+        let from = graph.nodes[&from];
+        let to = graph.nodes[&to];
+        let exists = graph
+            .graph
+            .edges(from)
+            .any(|e| e.target() == to && *e.weight() == kind);
+        if !exists {
+            graph.graph.add_edge(from, to, kind);
+        }
+    }
+
+    if target.is_some() {
+        graph.target_count += 1;
+    }
+}
+
+/// Returns the feature resolver version the workspace uses, assuming the original one if the
+/// workspace's manifest can't be read.
+fn resolver_version(args: &Args, workspace_root: &Path) -> u32 {
+    match read_resolver_version(workspace_root) {
+        Ok(version) => version,
+        Err(e) => {
+            if !args.quiet {
+                eprintln!("warning: {:#}, assuming resolver 1", e);
             }
+            1
+        }
+    }
+}
+
+fn read_resolver_version(workspace_root: &Path) -> Result<u32, Error> {
+    let path = workspace_root.join("Cargo.toml");
+    let manifest = fs::read_to_string(&path)
+        .with_context(|| format!("error reading {}", path.display()))?
+        .parse::<Value>()
+        .with_context(|| format!("error parsing {}", path.display()))?;
+
+    let resolver = ["workspace", "package"]
+        .iter()
+        .filter_map(|table| manifest.get(table)?.get("resolver")?.as_str())
+        .next();
+    if let Some(resolver) = resolver {
+        return Ok(resolver.parse().unwrap_or(1));
+    }
+
+    // the 2021 edition defaults to resolver 2, and the edition may be inherited from the workspace
+    let edition = match manifest.get("package").and_then(|p| p.get("edition")) {
+        Some(Value::Table(edition)) if edition.get("workspace") == Some(&Value::Boolean(true)) => {
+            manifest
+                .get("workspace")
+                .and_then(|w| w.get("package"))
+                .and_then(|p| p.get("edition"))
+        }
+        edition => edition,
+    };
+    let edition = edition
+        .and_then(Value::as_str)
+        .and_then(|e| e.parse::<u32>().ok())
+        .unwrap_or(2015);
+    Ok(if edition >= 2021 { 2 } else { 1 })
+}
+
+/// Returns a copy of the graph where packages whose host and target builds have different
+/// features under resolver 2 have a separate node for the host build, with each dependency edge
+/// routed to the build it's for.
+///
+/// This is only meant for rendering the tree, since everything else expects a node per package.
+/// `None` is returned if there's nothing to split.
+pub fn split_platforms(args: &Args, graph: &Graph) -> Option<Graph> {
+    if resolver_version(args, &graph.workspace_root) < 2 {
+        return None;
+    }
+    let targets = graph.inputs.as_ref().map_or(&[][..], |i| &i.targets);
+    let features = features::resolve(args, graph, targets);
+    split(graph, &features)
+}
+
+/// Splits the graph according to the features resolved for each platform.
+fn split(graph: &Graph, features: &Features) -> Option<Graph> {
+    let mut split = Graph {
+        graph: graph.graph.clone(),
+        nodes: graph.nodes.clone(),
+        root: graph.root.clone(),
+        workspace_members: graph.workspace_members.clone(),
+        targets: graph.targets.clone(),
+        target_count: graph.target_count,
+        workspace_root: graph.workspace_root.clone(),
+        host_nodes: HashMap::new(),
+        inputs: None,
+    };
+
+    let mut host_nodes = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let host = features.get(&(idx, Platform::Host));
+        let target = features.get(&(idx, Platform::Target));
+        if host.is_none() || target.is_none() || host == target {
+            continue;
+        }
+
+        let package = graph.graph[idx].clone();
+        let id = package.id.clone();
+        let host_idx = split.graph.add_node(package);
+        split.host_nodes.insert(id, host_idx);
+        host_nodes.insert(idx, host_idx);
+    }
+
+    if host_nodes.is_empty() {
+        return None;
+    }
+
+    let node = |idx: NodeIndex, platform| match platform {
+        Platform::Host => host_nodes.get(&idx).cloned().unwrap_or(idx),
+        Platform::Target => idx,
+    };
+
+    split.graph.clear_edges();
+    for edge in graph.graph.edge_references() {
+        let (from, to) = (edge.source(), edge.target());
+
+        // packages the resolver didn't reach are left as they are
+        let mut platforms = [Platform::Host, Platform::Target]
+            .iter()
+            .cloned()
+            .filter(|p| features.contains_key(&(from, *p)))
+            .collect::<Vec<_>>();
+        if platforms.is_empty() {
+            platforms.push(Platform::Target);
+        }
+
+        for platform in platforms {
+            let dep_platform = features::dep_platform(*edge.weight(), &graph.graph[to], platform);
+            let (from, to) = (node(from, platform), node(to, dep_platform));
+            let exists = split
+                .graph
+                .edges(from)
+                .any(|e| e.target() == to && e.weight() == edge.weight());
+            if !exists {
+                split.graph.add_edge(from, to, *edge.weight());
+            }
+        }
+    }
+
+    Some(split)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::args::Args;
+    use cargo_metadata::DependencyKind::{Build, Normal};
+    use serde_json::json;
+    use structopt::StructOpt;
+
+    /// Creates a path package with a library target, with `fields` overriding the defaults.
+    pub fn package(name: &str, fields: serde_json::Value) -> Package {
+        let mut package = json!({
+            "name": name,
+            "version": "1.0.0",
+            "source": null,
+            "dependencies": [],
+            "targets": [{
+                "name": name,
+                "kind": ["lib"],
+                "src_path": format!("/ws/{}/src/lib.rs", name),
+            }],
+            "features": {},
+            "manifest_path": format!("/ws/{}/Cargo.toml", name),
+            "authors": [],
+            "categories": [],
+            "keywords": [],
+            "edition": "2018",
         });
+        for (key, value) in fields.as_object().unwrap() {
+            package[key] = value.clone();
+        }
+        package["id"] = json!(format!(
+            "{} {} (path+file:///ws/{})",
+            name,
+            package["version"].as_str().unwrap(),
+            name
+        ));
+
+        serde_json::from_value(package).unwrap()
     }
 
-    Ok(graph)
+    /// Creates a graph from packages, the first of which is the root, and the dependencies
+    /// between them by name.
+    pub fn graph(packages: Vec<Package>, edges: &[(&str, &str, DependencyKind)]) -> Graph {
+        let mut graph = Graph {
+            graph: StableGraph::new(),
+            nodes: HashMap::new(),
+            root: Some(packages[0].id.clone()),
+            workspace_members: vec![packages[0].id.clone()],
+            targets: HashMap::new(),
+            target_count: 0,
+            workspace_root: PathBuf::from("/ws"),
+            host_nodes: HashMap::new(),
+            inputs: None,
+        };
+
+        let mut names = HashMap::new();
+        for package in packages {
+            names.insert(package.name.clone(), package.id.clone());
+            let id = package.id.clone();
+            let idx = graph.graph.add_node(package);
+            graph.nodes.insert(id, idx);
+        }
+        for (from, to, kind) in edges {
+            let from = graph.nodes[&names[*from]];
+            let to = graph.nodes[&names[*to]];
+            graph.graph.add_edge(from, to, *kind);
+        }
+
+        graph
+    }
+
+    /// Returns the node of the package with a name, which is its target build if it's split.
+    pub fn node(graph: &Graph, name: &str) -> NodeIndex {
+        graph
+            .nodes
+            .values()
+            .cloned()
+            .find(|idx| graph.graph[*idx].name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn split_host_builds() {
+        let dep = |kind: DependencyKind, feature: &str| {
+            json!({
+                "name": "lib",
+                "source": null,
+                "req": "^1.0",
+                "kind": kind,
+                "optional": false,
+                "uses_default_features": true,
+                "features": [feature],
+                "target": null,
+                "rename": null,
+            })
+        };
+        let graph = graph(
+            vec![
+                package(
+                    "root",
+                    json!({ "dependencies": [dep(Normal, "std"), dep(Build, "build")] }),
+                ),
+                package("lib", json!({ "features": { "std": [], "build": [] } })),
+                package("other", json!({})),
+            ],
+            &[
+                ("root", "lib", Normal),
+                ("root", "lib", Build),
+                ("root", "other", Normal),
+                ("lib", "other", Normal),
+            ],
+        );
+        let args = Args::from_iter(&["tree"]);
+        let split_graph = split(&graph, &features::resolve(&args, &graph, &[])).unwrap();
+
+        let lib = &graph.graph[node(&graph, "lib")].id;
+        let nodes = split_graph.package_nodes(lib);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(split_graph.platform(nodes[0]), Some(Platform::Target));
+        assert_eq!(split_graph.platform(nodes[1]), Some(Platform::Host));
+        // packages with the same features on both are left alone
+        assert_eq!(split_graph.platform(node(&split_graph, "other")), None);
+
+        let mut edges = split_graph
+            .graph
+            .edge_references()
+            .map(|e| {
+                let name = |idx| {
+                    let platform = split_graph.platform(idx).map(|p| format!(" ({})", p));
+                    format!(
+                        "{}{}",
+                        split_graph.graph[idx].name,
+                        platform.unwrap_or_default()
+                    )
+                };
+                (name(e.source()), name(e.target()), *e.weight())
+            })
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|(from, to, _)| (from.clone(), to.clone()));
+        assert_eq!(
+            edges,
+            [
+                ("lib (host)".to_string(), "other".to_string(), Normal),
+                ("lib (target)".to_string(), "other".to_string(), Normal),
+                ("root".to_string(), "lib (host)".to_string(), Build),
+                ("root".to_string(), "lib (target)".to_string(), Normal),
+                ("root".to_string(), "other".to_string(), Normal),
+            ]
+        );
+
+        // nothing is split without differing features
+        let mut features = features::resolve(&args, &graph, &[]);
+        let idx = node(&graph, "lib");
+        let host = features[&(idx, Platform::Host)].clone();
+        features.insert((idx, Platform::Target), host);
+        assert!(split(&graph, &features).is_none());
+    }
 }
@@ -0,0 +1,159 @@
+use crate::args::Args;
+use crate::format::Pattern;
+use crate::graph::Graph;
+use crate::spec;
+use crate::tree;
+use anyhow::Error;
+use cargo_metadata::DependencyKind;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::EdgeDirection;
+use serde::Serialize;
+use std::collections::HashMap;
+
+const TEMPLATE: &str = include_str!("report.html");
+
+#[derive(Serialize)]
+struct Report<'a> {
+    title: String,
+    inverted: bool,
+    roots: Vec<usize>,
+    packages: Vec<ReportPackage<'a>>,
+}
+
+#[derive(Serialize)]
+struct ReportPackage<'a> {
+    id: &'a str,
+    name: &'a str,
+    version: String,
+    label: String,
+    source: Option<String>,
+    license: Option<&'a str>,
+    description: Option<&'a str>,
+    repository: Option<&'a str>,
+    manifest_path: String,
+    purl: String,
+    /// The index and kind of each package below this one in the tree.
+    children: Vec<(usize, &'static str)>,
+}
+
+/// Prints a self-contained HTML page for browsing the tree.
+pub fn print(args: &Args, graph: &Graph) -> Result<(), Error> {
+    let format = Pattern::new(&args.format)?;
+    let direction = if args.invert {
+        EdgeDirection::Incoming
+    } else {
+        EdgeDirection::Outgoing
+    };
+
+    let roots = if args.package.is_some() || graph.root.is_some() {
+        vec![graph.nodes[tree::find_root(args, graph)?]]
+    } else {
+        graph
+            .workspace_members
+            .iter()
+            .filter_map(|id| graph.nodes.get(id).cloned())
+            .collect()
+    };
+
+    // number the packages in the order they're reached from the roots
+    let mut indices = HashMap::new();
+    let mut order = vec![];
+    let mut stack = roots.iter().rev().cloned().collect::<Vec<_>>();
+    while let Some(idx) = stack.pop() {
+        if indices.contains_key(&idx) {
+            continue;
+        }
+        indices.insert(idx, order.len());
+        order.push(idx);
+
+        let mut children = children(graph, idx, direction);
+        children.reverse();
+        stack.extend(children.into_iter().map(|(idx, _)| idx));
+    }
+
+    let packages = order
+        .iter()
+        .map(|idx| {
+            let package = &graph.graph[*idx];
+            ReportPackage {
+                id: &package.id.repr,
+                name: &package.name,
+                version: package.version.to_string(),
+                label: format.display(package).to_string(),
+                source: package.source.as_ref().map(|s| s.to_string()),
+                license: package.license.as_deref(),
+                description: package.description.as_deref(),
+                repository: package.repository.as_deref(),
+                manifest_path: package.manifest_path.display().to_string(),
+                purl: spec::purl(package),
+                children: children(graph, *idx, direction)
+                    .into_iter()
+                    .map(|(idx, kind)| (indices[&idx], kind))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let title = match roots.as_slice() {
+        [root] => {
+            let package = &graph.graph[*root];
+            format!("{} v{}", package.name, package.version)
+        }
+        _ => graph.workspace_root.display().to_string(),
+    };
+
+    let report = Report {
+        title: title.clone(),
+        inverted: args.invert,
+        roots: roots.iter().map(|idx| indices[idx]).collect(),
+        packages,
+    };
+
+    // keep the data from closing the script element it's embedded in
+    let data = serde_json::to_string(&report)?.replace("</", "<\\/");
+    let html = TEMPLATE
+        .replace("{{title}}", &escape(&title))
+        .replace("{{data}}", &data);
+    print!("{}", html);
+
+    Ok(())
+}
+
+/// Returns the packages below a package in the tree, ordered as they'd be printed.
+fn children(
+    graph: &Graph,
+    idx: NodeIndex,
+    direction: EdgeDirection,
+) -> Vec<(NodeIndex, &'static str)> {
+    let mut children = graph
+        .graph
+        .edges_directed(idx, direction)
+        .map(|edge| {
+            let child = match direction {
+                EdgeDirection::Incoming => edge.source(),
+                EdgeDirection::Outgoing => edge.target(),
+            };
+            let kind = match edge.weight() {
+                DependencyKind::Build => (1, "build"),
+                DependencyKind::Development => (2, "dev"),
+                _ => (0, "normal"),
+            };
+            (kind, &graph.graph[child].id, child)
+        })
+        .collect::<Vec<_>>();
+    children.sort();
+    children.dedup();
+
+    children
+        .into_iter()
+        .map(|((_, kind), _, idx)| (idx, kind))
+        .collect()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,588 @@
+use crate::args::{Args, Charset};
+use crate::format::Pattern;
+use crate::graph::{self, Graph};
+use crate::tree::{self, Symbols, ASCII_SYMBOLS, UTF8_SYMBOLS};
+use anyhow::Error;
+use cargo_metadata::DependencyKind;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::EdgeDirection;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+const DETAIL_HEIGHT: usize = 8;
+
+struct Row {
+    path: Vec<NodeIndex>,
+    levels_continue: Vec<bool>,
+    kind: DependencyKind,
+    expanded: bool,
+}
+
+impl Row {
+    fn node(&self) -> NodeIndex {
+        *self.path.last().unwrap()
+    }
+
+    /// A row is a cycle if its package already appears further up its own path.
+    fn is_cycle(&self) -> bool {
+        let (last, rest) = self.path.split_last().unwrap();
+        rest.contains(last)
+    }
+}
+
+/// The tree as seen from a single root in a single direction.
+struct View {
+    direction: EdgeDirection,
+    rows: Vec<Row>,
+    selected: usize,
+    offset: usize,
+}
+
+impl View {
+    fn new(graph: &Graph, root: NodeIndex, direction: EdgeDirection) -> View {
+        let mut view = View {
+            direction,
+            rows: vec![Row {
+                path: vec![root],
+                levels_continue: vec![],
+                kind: DependencyKind::Normal,
+                expanded: false,
+            }],
+            selected: 0,
+            offset: 0,
+        };
+        view.expand(graph, 0);
+        view
+    }
+
+    fn expand(&mut self, graph: &Graph, i: usize) {
+        let row = &self.rows[i];
+        if row.expanded || row.is_cycle() {
+            return;
+        }
+
+        let deps = dependencies(graph, row.node(), self.direction);
+        let mut new_rows = vec![];
+        for (j, (dep, kind)) in deps.iter().enumerate() {
+            let mut path = row.path.clone();
+            path.push(*dep);
+            let mut levels_continue = row.levels_continue.clone();
+            levels_continue.push(j + 1 < deps.len());
+            new_rows.push(Row {
+                path,
+                levels_continue,
+                kind: *kind,
+                expanded: false,
+            });
+        }
+
+        self.rows[i].expanded = true;
+        self.rows.splice(i + 1..i + 1, new_rows);
+    }
+
+    fn collapse(&mut self, i: usize) {
+        let depth = self.rows[i].path.len();
+        let end = self.rows[i + 1..]
+            .iter()
+            .position(|r| r.path.len() <= depth)
+            .map_or(self.rows.len(), |end| i + 1 + end);
+
+        self.rows[i].expanded = false;
+        self.rows.drain(i + 1..end);
+        if self.selected >= end {
+            self.selected -= end - i - 1;
+        } else if self.selected > i {
+            self.selected = i;
+        }
+    }
+
+    fn parent(&self, i: usize) -> Option<usize> {
+        let depth = self.rows[i].path.len();
+        self.rows[..i].iter().rposition(|r| r.path.len() < depth)
+    }
+
+    /// Expands every row along a path from the root, returning the index of its final row.
+    fn reveal(&mut self, graph: &Graph, path: &[NodeIndex]) -> usize {
+        let mut i = 0;
+        for node in &path[1..] {
+            self.expand(graph, i);
+            let depth = self.rows[i].path.len() + 1;
+            let child = self.rows[i + 1..]
+                .iter()
+                .take_while(|r| r.path.len() >= depth)
+                .position(|r| r.path.len() == depth && r.node() == *node);
+            match child {
+                Some(child) => i += 1 + child,
+                None => break,
+            }
+        }
+        i
+    }
+
+    /// Returns the packages of the view in display order, along with the parent each one is
+    /// first reached from.
+    fn preorder(&self, graph: &Graph) -> (Vec<NodeIndex>, HashMap<NodeIndex, NodeIndex>) {
+        let root = self.rows[0].node();
+        let mut order = vec![];
+        let mut parents = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![root];
+
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            order.push(node);
+
+            for (dep, _) in dependencies(graph, node, self.direction).iter().rev() {
+                if !visited.contains(dep) {
+                    parents.entry(*dep).or_insert(node);
+                    stack.push(*dep);
+                }
+            }
+        }
+
+        (order, parents)
+    }
+
+    fn scroll(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if height > 0 && self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+    }
+}
+
+enum Mode {
+    Browse,
+    Search,
+}
+
+struct Browser<'a> {
+    graph: &'a Graph,
+    format: Pattern,
+    symbols: &'static Symbols,
+    views: Vec<View>,
+    mode: Mode,
+    query: String,
+    message: Option<String>,
+    height: usize,
+}
+
+impl<'a> Browser<'a> {
+    fn view(&mut self) -> &mut View {
+        self.views.last_mut().unwrap()
+    }
+
+    fn selected_node(&self) -> NodeIndex {
+        let view = self.views.last().unwrap();
+        view.rows[view.selected].node()
+    }
+
+    /// Handles a key press, returning `false` when the browser should exit.
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        self.message = None;
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+
+        match self.mode {
+            Mode::Browse => self.browse(key),
+            Mode::Search => {
+                self.search_input(key);
+                true
+            }
+        }
+    }
+
+    fn browse(&mut self, key: KeyEvent) -> bool {
+        let graph = self.graph;
+        let page = self.height.max(1);
+        let view = self.view();
+        let last = view.rows.len() - 1;
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => view.selected = view.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => view.selected = (view.selected + 1).min(last),
+            KeyCode::PageUp => view.selected = view.selected.saturating_sub(page),
+            KeyCode::PageDown => view.selected = (view.selected + page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => view.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => view.selected = last,
+            KeyCode::Right | KeyCode::Char('l') => {
+                let selected = view.selected;
+                view.expand(graph, selected);
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                let selected = view.selected;
+                if view.rows[selected].expanded {
+                    view.collapse(selected);
+                } else if let Some(parent) = view.parent(selected) {
+                    view.selected = parent;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let selected = view.selected;
+                if view.rows[selected].expanded {
+                    view.collapse(selected);
+                } else {
+                    view.expand(graph, selected);
+                }
+            }
+            KeyCode::Char('i') => {
+                let node = view.rows[view.selected].node();
+                let direction = match view.direction {
+                    EdgeDirection::Outgoing => EdgeDirection::Incoming,
+                    EdgeDirection::Incoming => EdgeDirection::Outgoing,
+                };
+                self.views.push(View::new(graph, node, direction));
+            }
+            KeyCode::Backspace | KeyCode::Char('u') if self.views.len() > 1 => {
+                self.views.pop();
+            }
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search;
+                self.query.clear();
+            }
+            KeyCode::Char('n') => self.search(true, false),
+            KeyCode::Char('N') => self.search(false, false),
+            _ => {}
+        }
+
+        true
+    }
+
+    fn search_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.mode = Mode::Browse,
+            KeyCode::Esc => {
+                self.mode = Mode::Browse;
+                self.query.clear();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.search(true, true);
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.search(true, true);
+            }
+            _ => {}
+        }
+    }
+
+    /// Selects the next package whose name contains the search query, expanding the tree as
+    /// necessary to show it.
+    fn search(&mut self, forward: bool, inclusive: bool) {
+        if self.query.is_empty() {
+            return;
+        }
+
+        let graph = self.graph;
+        let query = self.query.to_lowercase();
+        let view = self.view();
+        let (order, parents) = view.preorder(graph);
+
+        let current = view.rows[view.selected].node();
+        let start = order.iter().position(|n| *n == current).unwrap_or(0);
+        let len = order.len();
+        let skip = if inclusive { 0 } else { 1 };
+
+        let found = (skip..len + skip)
+            .map(|i| {
+                if forward {
+                    order[(start + i) % len]
+                } else {
+                    order[(start + len - i % len) % len]
+                }
+            })
+            .find(|n| graph.graph[*n].name.to_lowercase().contains(&query));
+
+        let node = match found {
+            Some(node) => node,
+            None => {
+                self.message = Some(format!("no match for `{}`", self.query));
+                return;
+            }
+        };
+
+        let mut path = vec![node];
+        while let Some(parent) = parents.get(path.last().unwrap()) {
+            path.push(*parent);
+        }
+        path.reverse();
+
+        view.selected = view.reveal(graph, &path);
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<(), Error> {
+        let (width, height) = terminal::size()?;
+        let width = width as usize;
+        let height = height as usize;
+        self.height = height.saturating_sub(DETAIL_HEIGHT + 2);
+
+        let tree_height = self.height;
+        let view = self.view();
+        view.scroll(tree_height);
+
+        queue!(out, Clear(ClearType::All))?;
+
+        let view = self.views.last().unwrap();
+        for (line, i) in (view.offset..view.rows.len()).take(tree_height).enumerate() {
+            let text = self.row_text(&view.rows[i]);
+            queue!(out, MoveTo(0, line as u16))?;
+            if i == view.selected {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(out, Print(truncate(&text, width)))?;
+            if i == view.selected {
+                queue!(out, SetAttribute(Attribute::NoReverse))?;
+            }
+        }
+
+        queue!(
+            out,
+            MoveTo(0, tree_height as u16),
+            Print(self.symbols.right.repeat(width))
+        )?;
+
+        for (line, text) in self.details().iter().take(DETAIL_HEIGHT).enumerate() {
+            queue!(
+                out,
+                MoveTo(0, (tree_height + 1 + line) as u16),
+                Print(truncate(text, width))
+            )?;
+        }
+
+        let status = match (&self.mode, &self.message) {
+            (Mode::Search, _) => format!("/{}", self.query),
+            (Mode::Browse, Some(message)) => message.clone(),
+            (Mode::Browse, None) => {
+                "q: quit  ←/→: collapse/expand  /: search  n/N: next/previous match  \
+                 i: invert here  u: back"
+                    .to_string()
+            }
+        };
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(1) as u16),
+            SetAttribute(Attribute::Bold),
+            Print(truncate(&status, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        out.flush()?;
+        Ok(())
+    }
+
+    fn row_text(&self, row: &Row) -> String {
+        let mut text = String::new();
+
+        if let Some((last_continues, rest)) = row.levels_continue.split_last() {
+            for continues in rest {
+                let c = if *continues { self.symbols.down } else { " " };
+                text.push_str(c);
+                text.push_str("   ");
+            }
+
+            let c = if *last_continues {
+                self.symbols.tee
+            } else {
+                self.symbols.ell
+            };
+            text.push_str(c);
+            text.push_str(self.symbols.right);
+            text.push_str(self.symbols.right);
+            text.push(' ');
+        }
+
+        let view = self.views.last().unwrap();
+        let node = row.node();
+        let marker = if row.is_cycle() {
+            " "
+        } else if row.expanded {
+            "-"
+        } else if self
+            .graph
+            .graph
+            .edges_directed(node, view.direction)
+            .next()
+            .is_some()
+        {
+            "+"
+        } else {
+            " "
+        };
+        text.push_str(marker);
+        text.push(' ');
+
+        let package = &self.graph.graph[node];
+        text.push_str(&self.format.display(package).to_string());
+
+        match row.kind {
+            DependencyKind::Build => text.push_str(" (build)"),
+            DependencyKind::Development => text.push_str(" (dev)"),
+            _ => {}
+        }
+        if let Some(platform) = self.graph.platform(node) {
+            text.push_str(&format!(" ({})", platform));
+        }
+        if let Some(targets) = self.graph.partial_targets(&package.id) {
+            text.push_str(&format!(" [{}]", targets.join(", ")));
+        }
+        if row.is_cycle() {
+            text.push_str(" (*)");
+        }
+
+        text
+    }
+
+    fn details(&self) -> Vec<String> {
+        let idx = self.selected_node();
+        let package = &self.graph.graph[idx];
+        let dependencies = self
+            .graph
+            .graph
+            .edges_directed(idx, EdgeDirection::Outgoing)
+            .count();
+        let dependents = self
+            .graph
+            .graph
+            .edges_directed(idx, EdgeDirection::Incoming)
+            .count();
+
+        let mut details = vec![self.format.display(package).to_string()];
+        if let Some(description) = &package.description {
+            details.push(description.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        let source = match &package.source {
+            Some(source) => source.to_string(),
+            None => package.manifest_path.display().to_string(),
+        };
+        details.push(format!("source: {}", source));
+        if let Some(license) = &package.license {
+            details.push(format!("license: {}", license));
+        }
+        if let Some(repository) = &package.repository {
+            details.push(format!("repository: {}", repository));
+        }
+        if !package.authors.is_empty() {
+            details.push(format!("authors: {}", package.authors.join(", ")));
+        }
+        details.push(format!(
+            "dependencies: {}, dependents: {}",
+            dependencies, dependents
+        ));
+        if let Some(platform) = self.graph.platform(idx) {
+            details.push(format!("built for: {}", platform));
+        }
+        if let Some(targets) = self.graph.partial_targets(&package.id) {
+            details.push(format!("targets: {}", targets.join(", ")));
+        }
+
+        details
+    }
+}
+
+/// Restores the terminal even if the browser exits with an error.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+pub fn run(args: &Args, graph: &Graph) -> Result<(), Error> {
+    let format = Pattern::new(&args.format)?;
+
+    let direction = if args.invert {
+        EdgeDirection::Incoming
+    } else {
+        EdgeDirection::Outgoing
+    };
+
+    let symbols = match args.charset {
+        Charset::Utf8 => &UTF8_SYMBOLS,
+        Charset::Ascii => &ASCII_SYMBOLS,
+    };
+
+    let root = tree::find_root(args, graph)?;
+    // the browser shows the host and target builds of packages separately
+    let split = graph::split_platforms(args, graph);
+    let graph = split.as_ref().unwrap_or(graph);
+    let root = graph.nodes[root];
+
+    let mut browser = Browser {
+        graph,
+        format,
+        symbols,
+        views: vec![View::new(graph, root, direction)],
+        mode: Mode::Browse,
+        query: String::new(),
+        message: None,
+        height: 0,
+    };
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    let _guard = TerminalGuard;
+    execute!(stdout, EnterAlternateScreen, Hide)?;
+
+    loop {
+        browser.draw(&mut stdout)?;
+        if let Event::Key(key) = event::read()? {
+            if !browser.handle(key) {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn dependencies(
+    graph: &Graph,
+    idx: NodeIndex,
+    direction: EdgeDirection,
+) -> Vec<(NodeIndex, DependencyKind)> {
+    let mut deps = vec![];
+
+    for kind in &[
+        DependencyKind::Normal,
+        DependencyKind::Build,
+        DependencyKind::Development,
+    ] {
+        let mut kind_deps = vec![];
+        for edge in graph.graph.edges_directed(idx, direction) {
+            if edge.weight() != kind {
+                continue;
+            }
+
+            let dep = match direction {
+                EdgeDirection::Incoming => edge.source(),
+                EdgeDirection::Outgoing => edge.target(),
+            };
+            kind_deps.push((dep, *kind));
+        }
+
+        // ensure a consistent output ordering
+        kind_deps.sort_by_key(|(dep, _)| (&graph.graph[*dep].id, graph.platform(*dep)));
+        deps.extend(kind_deps);
+    }
+
+    deps
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
@@ -0,0 +1,70 @@
+use crate::graph::Graph;
+use anyhow::{Context, Error};
+use cargo_metadata::PackageId;
+use std::collections::HashMap;
+use std::fs;
+use toml::Value;
+
+/// Returns the SHA-256 checksums recorded in `Cargo.lock` for the packages in the graph.
+///
+/// Only packages from registries have checksums, and there are none if there is no lockfile.
+pub fn checksums(graph: &Graph) -> Result<HashMap<&PackageId, String>, Error> {
+    let path = graph.workspace_root.join("Cargo.lock");
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let lock = contents
+        .parse::<Value>()
+        .with_context(|| format!("error parsing {}", path.display()))?;
+
+    let mut by_key = HashMap::new();
+    // newer lockfiles record the checksum alongside each package
+    for package in lock
+        .get("package")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let field = |name| package.get(name).and_then(Value::as_str);
+        if let (Some(name), Some(version), Some(source), Some(checksum)) = (
+            field("name"),
+            field("version"),
+            field("source"),
+            field("checksum"),
+        ) {
+            by_key.insert(key(name, version, source), checksum.to_string());
+        }
+    }
+    // older ones record them in the metadata table as `"checksum name version (source)" = "..."`
+    for (k, checksum) in lock
+        .get("metadata")
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+    {
+        if let (Some(k), Some(checksum)) = (k.strip_prefix("checksum "), checksum.as_str()) {
+            by_key.insert(k.to_string(), checksum.to_string());
+        }
+    }
+
+    let mut checksums = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let package = &graph.graph[idx];
+        let source = match &package.source {
+            Some(source) => source,
+            None => continue,
+        };
+
+        let key = key(&package.name, &package.version.to_string(), &source.repr);
+        if let Some(checksum) = by_key.get(&key) {
+            checksums.insert(&package.id, checksum.clone());
+        }
+    }
+
+    Ok(checksums)
+}
+
+fn key(name: &str, version: &str, source: &str) -> String {
+    format!("{} {} ({})", name, version, source)
+}
@@ -1,18 +1,81 @@
-use crate::args::Opts;
+use crate::args::{Args, Command, Opts, OutputFormat};
+use crate::diagram::Diagram;
+use crate::graph::Graph;
 use anyhow::Error;
 use structopt::StructOpt;
 
+mod advisories;
 mod args;
+mod cfg;
+mod critical;
+mod cyclonedx;
+mod diagram;
+mod features;
 mod format;
 mod graph;
+mod html;
+mod interactive;
+mod lockfile;
 mod metadata;
+mod outdated;
+mod serve;
+mod size;
+mod spdx;
+mod spec;
+mod stats;
+mod table;
+mod topo;
 mod tree;
+mod unit_graph;
+mod unsafe_code;
+mod weight;
 
 fn main() -> Result<(), Error> {
     let Opts::Tree(args) = Opts::from_args();
-    let metadata = metadata::get(&args)?;
-    let graph = graph::build(&args, metadata)?;
-    tree::print(&args, &graph)?;
+    match &args.command {
+        Some(Command::Serve(serve)) => {
+            let graph = build_graph(&args)?;
+            serve::run(&args, serve, &graph)
+        }
+        None if args.unit_graph.is_some() => unit_graph::print(&args),
+        None => {
+            let graph = build_graph(&args)?;
+            run(&args, &graph)
+        }
+    }
+}
+
+fn build_graph(args: &Args) -> Result<Graph, Error> {
+    let metadata = metadata::get(args)?;
+    let targets = metadata::targets(args)?;
+    graph::build(args, metadata, targets)
+}
+
+fn run(args: &Args, graph: &Graph) -> Result<(), Error> {
+    if args.interactive {
+        interactive::run(args, graph)?;
+    } else if args.stats {
+        stats::print(args, graph)?;
+    } else if args.weight {
+        weight::print(args, graph)?;
+    } else if args.topo {
+        topo::print(args, graph)?;
+    } else if args.critical_path {
+        critical::print(args, graph)?;
+    } else {
+        match args.output_format {
+            OutputFormat::Tree => tree::print(args, graph)?,
+            OutputFormat::CycloneDxJson => cyclonedx::print(args, graph, false)?,
+            OutputFormat::CycloneDxXml => cyclonedx::print(args, graph, true)?,
+            OutputFormat::Spdx => spdx::print(args, graph, false)?,
+            OutputFormat::SpdxJson => spdx::print(args, graph, true)?,
+            OutputFormat::Mermaid => diagram::print(args, graph, Diagram::Mermaid)?,
+            OutputFormat::PlantUml => diagram::print(args, graph, Diagram::PlantUml)?,
+            OutputFormat::Html => html::print(args, graph)?,
+            OutputFormat::Csv => table::print(args, graph, ',')?,
+            OutputFormat::Tsv => table::print(args, graph, '\t')?,
+        }
+    }
 
     Ok(())
 }
@@ -1,4 +1,5 @@
 use crate::args::Args;
+use crate::cfg::{Cfg, Target};
 use anyhow::{anyhow, Context, Error};
 use cargo_metadata::Metadata;
 use std::env;
@@ -6,11 +7,42 @@ use std::ffi::OsString;
 use std::process::{Command, Stdio};
 
 pub fn get(args: &Args) -> Result<Metadata, Error> {
-    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
-
-    let mut command = Command::new(cargo);
+    let mut command = cargo();
     command.arg("metadata").arg("--format-version").arg("1");
+    common_args(&mut command, args);
+
+    let output = output(&mut command, "cargo metadata")?;
+
+    serde_json::from_str(&output).context("error parsing cargo metadata output")
+}
+
+/// Returns the JSON unit graph of the compilation units `cargo build` would run.
+///
+/// This requires a nightly Cargo.
+pub fn unit_graph(args: &Args) -> Result<String, Error> {
+    let mut command = cargo();
+    command.arg("build").arg("--unit-graph");
+    if !args.unstable_flags.iter().any(|f| f == "unstable-options") {
+        command.arg("-Z").arg("unstable-options");
+    }
+    common_args(&mut command, args);
+
+    if let Some(package) = &args.package {
+        command.arg("--package").arg(package);
+    }
+    for target in &args.target {
+        command.arg("--target").arg(target);
+    }
 
+    output(&mut command, "cargo build --unit-graph")
+}
+
+fn cargo() -> Command {
+    Command::new(env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo")))
+}
+
+/// Forwards the arguments shared by all Cargo commands.
+fn common_args(command: &mut Command, args: &Args) {
     if args.quiet {
         command.arg("-q");
     }
@@ -25,19 +57,6 @@ pub fn get(args: &Args) -> Result<Metadata, Error> {
         command.arg("--no-default-features");
     }
 
-    if !args.all_targets {
-        command.arg("--filter-platform");
-        match &args.target {
-            Some(target) => {
-                command.arg(target);
-            }
-            None => {
-                let target = default_target()?;
-                command.arg(target);
-            }
-        }
-    }
-
     if let Some(path) = &args.manifest_path {
         command.arg("--manifest-path").arg(path);
     }
@@ -47,7 +66,7 @@ pub fn get(args: &Args) -> Result<Metadata, Error> {
     }
 
     if let Some(color) = &args.color {
-        command.arg("--color").arg(color);
+        command.arg("--color").arg(color.as_str());
     }
 
     if args.frozen {
@@ -63,26 +82,77 @@ pub fn get(args: &Args) -> Result<Metadata, Error> {
     for flag in &args.unstable_flags {
         command.arg("-Z").arg(flag);
     }
+}
 
-    let output = output(&mut command, "cargo metadata")?;
+/// Returns the targets the graph should be resolved for, or nothing if `--all-targets` was passed.
+pub fn targets(args: &Args) -> Result<Vec<Target>, Error> {
+    if args.all_targets {
+        return Ok(vec![]);
+    }
 
-    serde_json::from_str(&output).context("error parsing cargo metadata output")
+    let names = if args.target.is_empty() {
+        vec![default_target()?]
+    } else {
+        args.target.clone()
+    };
+
+    let mut targets = vec![];
+    for name in names {
+        let cfg = target_cfg(&name)?;
+        targets.push(Target { name, cfg });
+    }
+
+    Ok(targets)
+}
+
+fn target_cfg(target: &str) -> Result<Vec<Cfg>, Error> {
+    let mut command = Command::new(rustc());
+    command
+        .arg("--print")
+        .arg("cfg")
+        .arg("--target")
+        .arg(target);
+
+    // custom cfgs are reported back by rustc alongside the target's own
+    if let Some(rustflags) = env::var_os("RUSTFLAGS") {
+        let rustflags = rustflags.to_string_lossy();
+        let mut it = rustflags.split_whitespace();
+        while let Some(flag) = it.next() {
+            if flag == "--cfg" {
+                if let Some(cfg) = it.next() {
+                    command.arg("--cfg").arg(cfg);
+                }
+            } else if flag.starts_with("--cfg=") {
+                command.arg(flag);
+            }
+        }
+    }
+
+    let output = output(&mut command, "rustc")?;
+
+    output
+        .lines()
+        .map(|line| line.parse())
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("error parsing rustc cfg output for target `{}`", target))
 }
 
 fn default_target() -> Result<String, Error> {
-    let rustc = env::var_os("RUSTC").unwrap_or_else(|| OsString::from("rustc"));
-    let output = output(Command::new(rustc).arg("-Vv"), "rustc")?;
+    let output = output(Command::new(rustc()).arg("-Vv"), "rustc")?;
 
     for line in output.lines() {
-        let prefix = "host: ";
-        if line.starts_with(prefix) {
-            return Ok(line[prefix.len()..].trim().to_string());
+        if let Some(host) = line.strip_prefix("host: ") {
+            return Ok(host.trim().to_string());
         }
     }
 
     Err(anyhow!("host missing from rustc output"))
 }
 
+fn rustc() -> OsString {
+    env::var_os("RUSTC").unwrap_or_else(|| OsString::from("rustc"))
+}
+
 fn output(command: &mut Command, job: &str) -> Result<String, Error> {
     let output = command
         .stderr(Stdio::inherit())
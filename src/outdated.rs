@@ -0,0 +1,316 @@
+use crate::graph::Graph;
+use anyhow::{anyhow, Context, Error};
+use petgraph::graph::NodeIndex;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The only version of Cargo's index cache format which is understood.
+const CACHE_VERSION: u8 = 3;
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+/// How a locked package compares to the versions published in the registry.
+pub struct Status {
+    /// The newest non-yanked version semver-compatible with the locked version.
+    pub compatible: Option<Version>,
+    /// The newest non-yanked version.
+    pub latest: Option<Version>,
+    pub yanked: bool,
+    locked: Version,
+}
+
+impl Status {
+    /// Determines if the locked version is yanked or there is a newer version available.
+    pub fn lags(&self) -> bool {
+        self.yanked || self.latest.as_ref().is_some_and(|v| *v > self.locked)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if self.yanked {
+            parts.push("yanked".to_string());
+        }
+
+        let compatible = self.compatible.as_ref().filter(|v| **v > self.locked);
+        let latest = self.latest.as_ref().filter(|v| **v > self.locked);
+        match (compatible, latest) {
+            (None, None) => {}
+            (Some(compatible), Some(latest)) if compatible == latest => {
+                parts.push(format!("outdated: {} available", latest))
+            }
+            (Some(compatible), Some(latest)) => parts.push(format!(
+                "outdated: {} compatible, {} latest",
+                compatible, latest
+            )),
+            (None, Some(latest)) => parts.push(format!("outdated: {} latest", latest)),
+            (Some(compatible), None) => parts.push(format!("outdated: {} compatible", compatible)),
+        }
+
+        if parts.is_empty() {
+            fmt.write_str("up to date")
+        } else {
+            fmt.write_str(&parts.join("; "))
+        }
+    }
+}
+
+/// Compares each crates.io package against the versions in Cargo's local cache of the index.
+///
+/// Packages whose index entries haven't been cached are left out.
+pub fn find(graph: &Graph) -> Result<HashMap<NodeIndex, Status>, Error> {
+    let indexes = index_caches()?;
+
+    let mut statuses = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let package = &graph.graph[idx];
+        if !package.source.as_ref().is_some_and(|s| s.is_crates_io()) {
+            continue;
+        }
+
+        let path = indexes
+            .iter()
+            .map(|dir| dir.join(cache_path(&package.name)))
+            .find(|path| path.is_file());
+        let path = match path {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let entries = read_cache(&path)
+            .with_context(|| format!("error reading index cache {}", path.display()))?;
+        statuses.insert(idx, status(&package.version, &entries));
+    }
+
+    Ok(statuses)
+}
+
+fn status(locked: &Version, entries: &[IndexEntry]) -> Status {
+    // a caret requirement is exactly the range of versions compatible with the locked one
+    let compatible_req = VersionReq::parse(&format!("^{}", locked)).ok();
+
+    let mut compatible = None::<Version>;
+    let mut latest = None::<Version>;
+    let mut yanked = false;
+    for entry in entries {
+        let version = match Version::parse(&entry.vers) {
+            Ok(version) => version,
+            Err(_) => continue,
+        };
+
+        if version == *locked {
+            yanked = entry.yanked;
+        }
+        if entry.yanked || version.is_prerelease() {
+            continue;
+        }
+
+        if compatible_req.as_ref().is_some_and(|r| r.matches(&version))
+            && compatible.as_ref().is_none_or(|v| version > *v)
+        {
+            compatible = Some(version.clone());
+        }
+        if latest.as_ref().is_none_or(|v| version > *v) {
+            latest = Some(version);
+        }
+    }
+
+    Status {
+        compatible,
+        latest,
+        yanked,
+        locked: locked.clone(),
+    }
+}
+
+/// Returns the cache directories of Cargo's local copies of the crates.io index.
+fn index_caches() -> Result<Vec<PathBuf>, Error> {
+    let index = cargo_home()?.join("registry").join("index");
+
+    let mut caches = vec![];
+    if let Ok(entries) = fs::read_dir(&index) {
+        for entry in entries {
+            let entry = entry?;
+            if !is_crates_io_index(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+
+            let cache = entry.path().join(".cache");
+            if cache.is_dir() {
+                caches.push(cache);
+            }
+        }
+    }
+    // prefer the sparse index, which is what newer Cargos use
+    caches.sort_by_key(|cache| !cache.starts_with(index.join("index.crates.io")));
+
+    if caches.is_empty() {
+        return Err(anyhow!(
+            "no crates.io index cache found in {}",
+            index.display()
+        ));
+    }
+
+    Ok(caches)
+}
+
+/// Determines if a directory in Cargo's registry index directory holds the crates.io index, so
+/// that crates of the same name in other registries aren't mistaken for crates.io's.
+///
+/// Directories are named after the host of the index followed by a hash of its source. The hash
+/// depends on the Cargo version, so the git index on GitHub is recognized by the hashes Cargo is
+/// known to have used for it, while the sparse index's host is unique to crates.io.
+fn is_crates_io_index(name: &str) -> bool {
+    const GIT_HASHES: &[&str] = &["1ecc6299db9ec823", "25cdd57fae9f0462"];
+
+    match name.rsplit_once('-') {
+        Some(("index.crates.io", _)) => true,
+        Some(("github.com", hash)) => GIT_HASHES.contains(&hash),
+        _ => false,
+    }
+}
+
+fn cargo_home() -> Result<PathBuf, Error> {
+    if let Some(home) = env::var_os("CARGO_HOME") {
+        return Ok(PathBuf::from(home));
+    }
+
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| Path::new(&home).join(".cargo"))
+        .ok_or_else(|| anyhow!("unable to determine the Cargo home directory"))
+}
+
+/// Returns the path of a crate's entry relative to the root of the index.
+fn cache_path(name: &str) -> PathBuf {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => Path::new("1").join(&name),
+        2 => Path::new("2").join(&name),
+        3 => Path::new("3").join(&name[..1]).join(&name),
+        _ => Path::new(&name[..2]).join(&name[2..4]).join(&name),
+    }
+}
+
+/// Parses a cache file, which consists of a header followed by NUL-terminated pairs of a version
+/// and its JSON index entry.
+fn read_cache(path: &Path) -> Result<Vec<IndexEntry>, Error> {
+    parse_cache(&fs::read(path)?)
+}
+
+fn parse_cache(contents: &[u8]) -> Result<Vec<IndexEntry>, Error> {
+    let (version, rest) = contents
+        .split_first()
+        .ok_or_else(|| anyhow!("empty cache file"))?;
+    if *version != CACHE_VERSION {
+        return Err(anyhow!("unsupported cache version {}", version));
+    }
+    // skip the index format version and the index revision the cache was created from
+    let rest = rest
+        .get(4..)
+        .ok_or_else(|| anyhow!("truncated cache file"))?;
+    let mut fields = rest.split(|b| *b == 0).skip(1);
+
+    let mut entries = vec![];
+    while let (Some(_), Some(json)) = (fields.next(), fields.next()) {
+        entries.push(serde_json::from_slice(json)?);
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cache file for versions 1.0.0, 1.1.0 (yanked), 1.2.0, 2.0.0-beta.1 and 2.0.0.
+    const CACHE: &[u8] = b"\x03\x02\x00\x00\x00etag: \"abc\"\x00\
+        1.0.0\x00{\"name\":\"foo\",\"vers\":\"1.0.0\",\"deps\":[],\"cksum\":\"00\",\"features\":{},\"yanked\":false}\x00\
+        1.1.0\x00{\"name\":\"foo\",\"vers\":\"1.1.0\",\"deps\":[],\"cksum\":\"00\",\"features\":{},\"yanked\":true}\x00\
+        1.2.0\x00{\"name\":\"foo\",\"vers\":\"1.2.0\",\"deps\":[],\"cksum\":\"00\",\"features\":{}}\x00\
+        2.0.0-beta.1\x00{\"name\":\"foo\",\"vers\":\"2.0.0-beta.1\",\"deps\":[],\"cksum\":\"00\",\"features\":{}}\x00\
+        2.0.0\x00{\"name\":\"foo\",\"vers\":\"2.0.0\",\"deps\":[],\"cksum\":\"00\",\"features\":{}}\x00";
+
+    #[test]
+    fn cache() {
+        let entries = parse_cache(CACHE).unwrap();
+        let versions = entries
+            .iter()
+            .map(|e| (&*e.vers, e.yanked))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            versions,
+            [
+                ("1.0.0", false),
+                ("1.1.0", true),
+                ("1.2.0", false),
+                ("2.0.0-beta.1", false),
+                ("2.0.0", false),
+            ]
+        );
+
+        let errors: &[&[u8]] = &[b"", b"\x02\x02\x00\x00\x00rev\x00", b"\x03\x02\x00"];
+        for contents in errors {
+            assert!(parse_cache(contents).is_err(), "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn statuses() {
+        let entries = parse_cache(CACHE).unwrap();
+        let cases = [
+            ("1.0.0", true, "outdated: 1.2.0 compatible, 2.0.0 latest"),
+            (
+                "1.1.0",
+                true,
+                "yanked; outdated: 1.2.0 compatible, 2.0.0 latest",
+            ),
+            ("1.2.0", true, "outdated: 2.0.0 latest"),
+            ("2.0.0-beta.1", true, "outdated: 2.0.0 available"),
+            ("2.0.0", false, "up to date"),
+            ("3.0.0", false, "up to date"),
+        ];
+
+        for (locked, lags, expected) in &cases {
+            let status = status(&Version::parse(locked).unwrap(), &entries);
+            assert_eq!(status.lags(), *lags, "{}", locked);
+            assert_eq!(status.to_string(), *expected, "{}", locked);
+        }
+
+        let yanked = [IndexEntry {
+            vers: "1.0.0".to_string(),
+            yanked: true,
+        }];
+        let status = status(&Version::parse("1.0.0").unwrap(), &yanked);
+        assert!(status.lags());
+        assert_eq!(status.to_string(), "yanked");
+    }
+
+    #[test]
+    fn crates_io_indexes() {
+        let cases = [
+            ("index.crates.io-1949cf8c6b5b557f", true),
+            ("index.crates.io-6f17d22bba15001f", true),
+            ("github.com-1ecc6299db9ec823", true),
+            ("github.com-25cdd57fae9f0462", true),
+            ("github.com-0123456789abcdef", false),
+            ("my-registry.example.com-0123456789abcdef", false),
+            ("index.crates.io", false),
+        ];
+
+        for (name, expected) in &cases {
+            assert_eq!(is_crates_io_index(name), *expected, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use crate::args::{Args, ServeArgs};
+use crate::format::Pattern;
+use crate::graph::{kind_name, Graph};
+use crate::spec;
+use crate::tree;
+use anyhow::{anyhow, Error};
+use cargo_metadata::DependencyKind;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::{Dfs, EdgeRef};
+use petgraph::EdgeDirection;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tiny_http::{Header, Request, Response, Server};
+
+const UI: &str = include_str!("ui.html");
+
+/// The most paths returned by `/api/paths`, since there can be exponentially many.
+const MAX_PATHS: usize = 100;
+
+#[derive(Serialize)]
+struct Node<'a> {
+    /// The package ID specification identifying the package in other requests.
+    spec: String,
+    name: &'a str,
+    version: String,
+    label: String,
+    source: Option<String>,
+    license: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Subtree<'a> {
+    #[serde(flatten)]
+    node: Node<'a>,
+    kind: &'static str,
+    /// Set if the package's dependencies were already listed elsewhere in the subtree.
+    repeated: bool,
+    children: Vec<Subtree<'a>>,
+}
+
+struct Api<'a> {
+    args: &'a Args,
+    graph: &'a Graph,
+    format: Pattern,
+}
+
+/// Serves the graph over HTTP until the process is killed.
+pub fn run(args: &Args, serve: &ServeArgs, graph: &Graph) -> Result<(), Error> {
+    let api = Api {
+        args,
+        graph,
+        format: Pattern::new(&args.format)?,
+    };
+
+    let addr = format!("{}:{}", serve.bind, serve.port);
+    let server = Server::http(&addr).map_err(|e| anyhow!("error listening on {}: {}", addr, e))?;
+    eprintln!("Serving the dependency graph on http://{}", addr);
+
+    for request in server.incoming_requests() {
+        let response = api.handle(&request);
+        // a client going away isn't a problem for the server
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+impl<'a> Api<'a> {
+    fn handle(&self, request: &Request) -> Response<std::io::Cursor<Vec<u8>>> {
+        let mut url = request.url().splitn(2, '?');
+        let path = url.next().unwrap();
+        let query = parse_query(url.next().unwrap_or(""));
+
+        let result = match path {
+            "/" => return respond(200, "text/html; charset=utf-8", UI.to_string()),
+            "/api/packages" => self.packages(&query),
+            "/api/package" => self.package(&query),
+            "/api/paths" => self.paths(&query),
+            "/api/subtree" => self.subtree(&query),
+            "/api/duplicates" => Ok(self.duplicates()),
+            _ => return json(404, &serde_json::json!({ "error": "not found" })),
+        };
+
+        match result {
+            Ok(value) => json(200, &value),
+            Err(e) => json(400, &serde_json::json!({ "error": e.to_string() })),
+        }
+    }
+
+    /// Lists the packages whose label contains the `q` parameter.
+    fn packages(&self, query: &HashMap<String, String>) -> Result<Value, Error> {
+        let q = query.get("q").map(|q| q.to_lowercase()).unwrap_or_default();
+
+        let mut nodes = self
+            .graph
+            .graph
+            .node_indices()
+            .map(|idx| self.node(idx))
+            .filter(|node| node.label.to_lowercase().contains(&q))
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.spec.cmp(&b.spec));
+
+        Ok(serde_json::to_value(nodes)?)
+    }
+
+    /// Returns a package along with the packages it depends on and that depend on it.
+    fn package(&self, query: &HashMap<String, String>) -> Result<Value, Error> {
+        let idx = self.find(query, "spec")?;
+        let package = &self.graph.graph[idx];
+
+        let neighbors = |direction| {
+            let mut neighbors = self
+                .graph
+                .graph
+                .edges_directed(idx, direction)
+                .map(|edge| {
+                    let other = match direction {
+                        EdgeDirection::Incoming => edge.source(),
+                        EdgeDirection::Outgoing => edge.target(),
+                    };
+                    (self.node(other), kind_name(*edge.weight()))
+                })
+                .map(|(node, kind)| serde_json::json!({ "package": node, "kind": kind }))
+                .collect::<Vec<_>>();
+            neighbors.sort_by(|a, b| {
+                a["package"]["spec"]
+                    .as_str()
+                    .cmp(&b["package"]["spec"].as_str())
+            });
+            neighbors
+        };
+
+        Ok(serde_json::json!({
+            "package": self.node(idx),
+            "description": package.description,
+            "repository": package.repository,
+            "manifest_path": package.manifest_path,
+            "purl": spec::purl(package),
+            "dependencies": neighbors(EdgeDirection::Outgoing),
+            "dependents": neighbors(EdgeDirection::Incoming),
+        }))
+    }
+
+    /// Returns the paths from the `from` package, or the root package, to the `to` package.
+    fn paths(&self, query: &HashMap<String, String>) -> Result<Value, Error> {
+        let to = self.find(query, "to")?;
+        let sources = if query.contains_key("from") {
+            vec![self.find(query, "from")?]
+        } else {
+            self.roots()?
+        };
+
+        // only packages reachable from a source can lead back to one, which keeps the search from
+        // exploring every path to the root when `from` isn't an ancestor of `to`
+        let mut reachable = HashSet::new();
+        let mut dfs = Dfs::empty(&self.graph.graph);
+        for source in &sources {
+            dfs.move_to(*source);
+            while let Some(idx) = dfs.next(&self.graph.graph) {
+                reachable.insert(idx);
+            }
+        }
+
+        let mut paths = vec![];
+        let mut path = vec![to];
+        let mut on_path = HashSet::new();
+        on_path.insert(to);
+        if reachable.contains(&to) {
+            self.find_paths(&sources, &reachable, &mut path, &mut on_path, &mut paths);
+        }
+
+        // one more path than is returned is searched for to tell if any were left out
+        let truncated = paths.len() > MAX_PATHS;
+        paths.truncate(MAX_PATHS);
+
+        let paths = paths
+            .iter()
+            .map(|path| {
+                path.iter()
+                    .rev()
+                    .map(|idx| self.node(*idx))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        Ok(serde_json::json!({
+            "paths": paths,
+            "truncated": truncated,
+        }))
+    }
+
+    /// Walks up from the end of `path` through dependents, recording each way to a source.
+    fn find_paths(
+        &self,
+        sources: &[NodeIndex],
+        reachable: &HashSet<NodeIndex>,
+        path: &mut Vec<NodeIndex>,
+        on_path: &mut HashSet<NodeIndex>,
+        paths: &mut Vec<Vec<NodeIndex>>,
+    ) {
+        if paths.len() > MAX_PATHS {
+            return;
+        }
+
+        let last = *path.last().unwrap();
+        if sources.contains(&last) {
+            paths.push(path.clone());
+            return;
+        }
+
+        let mut dependents = self
+            .graph
+            .graph
+            .neighbors_directed(last, EdgeDirection::Incoming)
+            .collect::<Vec<_>>();
+        dependents.sort_by_key(|idx| &self.graph.graph[*idx].id);
+        dependents.dedup();
+
+        for dependent in dependents {
+            if reachable.contains(&dependent) && on_path.insert(dependent) {
+                path.push(dependent);
+                self.find_paths(sources, reachable, path, on_path, paths);
+                path.pop();
+                on_path.remove(&dependent);
+            }
+        }
+    }
+
+    /// Returns the tree below a package, or the root package, to an optional `depth`.
+    fn subtree(&self, query: &HashMap<String, String>) -> Result<Value, Error> {
+        let root = if query.contains_key("spec") {
+            self.find(query, "spec")?
+        } else {
+            self.graph.nodes[tree::find_root(self.args, self.graph)?]
+        };
+        let depth = match query.get("depth") {
+            Some(depth) => Some(depth.parse().map_err(|_| anyhow!("invalid depth"))?),
+            None => self.args.depth,
+        };
+        let direction = if self.args.invert {
+            EdgeDirection::Incoming
+        } else {
+            EdgeDirection::Outgoing
+        };
+
+        let mut visited = HashSet::new();
+        let subtree = self.build_subtree(root, "normal", direction, depth, &mut visited);
+        Ok(serde_json::to_value(subtree)?)
+    }
+
+    fn build_subtree(
+        &self,
+        idx: NodeIndex,
+        kind: &'static str,
+        direction: EdgeDirection,
+        depth: Option<usize>,
+        visited: &mut HashSet<NodeIndex>,
+    ) -> Subtree<'a> {
+        let repeated = !visited.insert(idx);
+
+        let mut children = vec![];
+        if !repeated && depth != Some(0) {
+            let mut edges = self
+                .graph
+                .graph
+                .edges_directed(idx, direction)
+                .map(|edge| {
+                    let other = match direction {
+                        EdgeDirection::Incoming => edge.source(),
+                        EdgeDirection::Outgoing => edge.target(),
+                    };
+                    (*edge.weight(), &self.graph.graph[other].id, other)
+                })
+                .collect::<Vec<_>>();
+            // match the terminal tree's ordering
+            edges.sort_by_key(|(kind, id, _)| (kind_order(*kind), *id));
+
+            for (kind, _, other) in edges {
+                let depth = depth.map(|d| d - 1);
+                children.push(self.build_subtree(
+                    other,
+                    kind_name(kind),
+                    direction,
+                    depth,
+                    visited,
+                ));
+            }
+        }
+
+        Subtree {
+            node: self.node(idx),
+            kind,
+            repeated,
+            children,
+        }
+    }
+
+    /// Lists the packages which appear in the graph in multiple versions, grouped by name.
+    fn duplicates(&self) -> Value {
+        let mut duplicates = BTreeMap::new();
+        for id in tree::find_duplicates(self.graph) {
+            let idx = self.graph.nodes[id];
+            duplicates
+                .entry(&*self.graph.graph[idx].name)
+                .or_insert_with(Vec::new)
+                .push(self.node(idx));
+        }
+
+        let duplicates = duplicates
+            .into_iter()
+            .map(|(name, versions)| serde_json::json!({ "name": name, "versions": versions }))
+            .collect::<Vec<_>>();
+        Value::Array(duplicates)
+    }
+
+    fn roots(&self) -> Result<Vec<NodeIndex>, Error> {
+        if self.args.package.is_some() || self.graph.root.is_some() {
+            Ok(vec![
+                self.graph.nodes[tree::find_root(self.args, self.graph)?],
+            ])
+        } else {
+            Ok(self
+                .graph
+                .workspace_members
+                .iter()
+                .filter_map(|id| self.graph.nodes.get(id).cloned())
+                .collect())
+        }
+    }
+
+    fn find(&self, query: &HashMap<String, String>, param: &str) -> Result<NodeIndex, Error> {
+        let spec = query
+            .get(param)
+            .ok_or_else(|| anyhow!("missing `{}` parameter", param))?;
+        let id = tree::find_package(spec, self.graph)?;
+        Ok(self.graph.nodes[id])
+    }
+
+    fn node(&self, idx: NodeIndex) -> Node<'a> {
+        let package = &self.graph.graph[idx];
+        Node {
+            spec: spec::package_spec(package),
+            name: &package.name,
+            version: package.version.to_string(),
+            label: self.format.display(package).to_string(),
+            source: package.source.as_ref().map(|s| s.to_string()),
+            license: package.license.as_deref(),
+        }
+    }
+}
+
+fn kind_order(kind: DependencyKind) -> u8 {
+    match kind {
+        DependencyKind::Build => 1,
+        DependencyKind::Development => 2,
+        _ => 0,
+    }
+}
+
+fn json(status: u16, value: &Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    respond(status, "application/json", value.to_string())
+}
+
+fn respond(status: u16, content_type: &str, body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes("Content-Type", content_type).unwrap();
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header)
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut it = pair.splitn(2, '=');
+            let key = percent_decode(it.next().unwrap());
+            let value = percent_decode(it.next().unwrap_or(""));
+            (key, value)
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], escaped) {
+            (_, Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b'+', None) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (b, None) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::{graph, package};
+    use structopt::StructOpt;
+
+    /// Searches a graph where the root reaches `leaf` through each of `count` packages.
+    fn paths(count: usize) -> Value {
+        let names = (0..count).map(|i| format!("dep{}", i)).collect::<Vec<_>>();
+        let mut packages = vec![package("root", serde_json::json!({}))];
+        let mut edges = vec![];
+        for name in &names {
+            packages.push(package(name, serde_json::json!({})));
+            edges.push(("root", &**name, DependencyKind::Normal));
+            edges.push((&**name, "leaf", DependencyKind::Normal));
+        }
+        packages.push(package("leaf", serde_json::json!({})));
+
+        let args = Args::from_iter(&["tree"]);
+        let api = Api {
+            args: &args,
+            graph: &graph(packages, &edges),
+            format: Pattern::new("{p}").unwrap(),
+        };
+        let query = parse_query("to=leaf");
+        api.paths(&query).unwrap()
+    }
+
+    #[test]
+    fn truncation() {
+        let found = paths(MAX_PATHS);
+        assert_eq!(found["paths"].as_array().unwrap().len(), MAX_PATHS);
+        assert_eq!(found["truncated"], false);
+
+        let found = paths(MAX_PATHS + 1);
+        assert_eq!(found["paths"].as_array().unwrap().len(), MAX_PATHS);
+        assert_eq!(found["truncated"], true);
+    }
+
+    #[test]
+    fn decode() {
+        let cases = [
+            ("serde", "serde"),
+            ("a+b", "a b"),
+            ("a%2Bb", "a+b"),
+            ("a%2bb", "a+b"),
+            ("serde%401.0", "serde@1.0"),
+            ("%E2%94%82", "\u{2502}"),
+            ("100%", "100%"),
+            ("%2", "%2"),
+            ("%zz", "%zz"),
+            ("%%41", "%A"),
+            ("%FF", "\u{fffd}"),
+        ];
+
+        for (s, expected) in &cases {
+            assert_eq!(percent_decode(s), *expected, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::graph::Graph;
+use crate::weight;
+use anyhow::{Context, Error};
+use cargo_metadata::PackageId;
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Default)]
+pub struct Size {
+    pub bytes: u64,
+    pub lines: u64,
+}
+
+impl AddAssign for Size {
+    fn add_assign(&mut self, other: Size) {
+        self.bytes += other.bytes;
+        self.lines += other.lines;
+    }
+}
+
+/// Returns annotations with the size of each package, along with the total size of it and the
+/// packages only it pulls in if there is a root package.
+pub fn annotations(
+    graph: &Graph,
+    root: Option<NodeIndex>,
+) -> Result<HashMap<&PackageId, String>, Error> {
+    let mut sizes = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let package = &graph.graph[idx];
+        let dir = package.manifest_path.parent().unwrap();
+        // the sources of packages which haven't been downloaded may not exist
+        if dir.is_dir() {
+            let size = measure(dir)
+                .with_context(|| format!("error measuring sources of `{}`", package.name))?;
+            sizes.insert(idx, size);
+        }
+    }
+
+    let dominated = match root {
+        Some(root) => weight::dominated(graph, root),
+        None => HashMap::new(),
+    };
+
+    let mut annotations = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let size = match sizes.get(&idx) {
+            Some(size) => *size,
+            None => {
+                annotations.insert(&graph.graph[idx].id, "size unavailable".to_string());
+                continue;
+            }
+        };
+
+        let mut annotation = format_size(size);
+        if let Some(dominated) = dominated.get(&idx) {
+            let mut exclusive = Size::default();
+            for idx in dominated {
+                exclusive += sizes.get(idx).cloned().unwrap_or_default();
+            }
+            annotation.push_str(&format!(", exclusive {}", format_size(exclusive)));
+        }
+        annotations.insert(&graph.graph[idx].id, annotation);
+    }
+
+    Ok(annotations)
+}
+
+/// Measures the Rust sources of a package.
+///
+/// Only lines which are neither blank nor entirely line or block comments are counted.
+pub fn measure(dir: &Path) -> Result<Size, Error> {
+    let mut size = Size::default();
+
+    for path in rust_sources(dir)? {
+        let contents = fs::read(&path)?;
+        size.bytes += contents.len() as u64;
+        size.lines += count_lines(&String::from_utf8_lossy(&contents));
+    }
+
+    Ok(size)
+}
+
+/// Counts the lines containing anything other than whitespace and comments.
+fn count_lines(source: &str) -> u64 {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut lines = 0;
+    // whether the current line has anything other than whitespace and comments
+    let mut code = false;
+    // the nesting depth of block comments
+    let mut depth = 0;
+    let mut string = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        if c == '\n' {
+            if code {
+                lines += 1;
+            }
+            code = false;
+        } else if depth > 0 {
+            if c == '/' && next == Some('*') {
+                depth += 1;
+                i += 1;
+            } else if c == '*' && next == Some('/') {
+                depth -= 1;
+                i += 1;
+            }
+        } else if string {
+            code = true;
+            if c == '\\' && next != Some('\n') {
+                i += 1;
+            } else if c == '"' {
+                string = false;
+            }
+        } else if c == '/' && next == Some('/') {
+            while chars.get(i + 1).is_some_and(|c| *c != '\n') {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            depth = 1;
+            i += 1;
+        } else if !c.is_whitespace() {
+            code = true;
+            if c == '"' {
+                string = true;
+            } else if c == '\'' && next == Some('\\') {
+                // an escaped character literal, which may be a quote
+                i += 2;
+            } else if c == '\'' && chars.get(i + 2) == Some(&'\'') {
+                i += 2;
+            }
+        }
+
+        i += 1;
+    }
+
+    if code {
+        lines += 1;
+    }
+    lines
+}
+
+/// Returns the paths of all Rust source files in a package directory, skipping build output,
+/// hidden directories and nested packages.
+pub fn rust_sources(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut sources = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            if name == "target" || name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.join("Cargo.toml").exists() {
+                continue;
+            }
+            sources.extend(rust_sources(&path)?);
+        } else if file_type.is_file() && path.extension() == Some(OsStr::new("rs")) {
+            sources.push(path);
+        }
+    }
+
+    Ok(sources)
+}
+
+fn format_size(size: Size) -> String {
+    format!("{}, {} lines", format_bytes(size.bytes), size.lines)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let cases = [
+            ("fn main() {}\n", 1),
+            ("\n  \n\tfn main() {}\n\n", 1),
+            ("// comment\n/// doc\n//! inner doc\nfn main() {}", 1),
+            ("fn main() {} // comment", 1),
+            ("/* block\n   comment */\nfn main() {}", 1),
+            ("/** doc\n * block\n */\nfn main() {}", 1),
+            ("/* outer /* nested */ still\n comment */ fn main() {}", 1),
+            ("fn main() {} /* trailing\n comment */", 1),
+            ("let s = \"/* not a\ncomment\";", 2),
+            ("let s = \"// not a comment\";", 1),
+            ("let s = \"\\\" /* still a string\";", 1),
+            ("let c = '\"'; /* comment\n */", 1),
+            ("let c = '\\''; // comment\nfn main() {}", 2),
+        ];
+
+        for (source, expected) in &cases {
+            assert_eq!(count_lines(source), *expected, "{:?}", source);
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use crate::args::Args;
+use crate::graph::Graph;
+use crate::lockfile;
+use crate::spec;
+use crate::tree;
+use anyhow::Error;
+use cargo_metadata::{DependencyKind, Package};
+use petgraph::visit::EdgeRef;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const NOASSERTION: &str = "NOASSERTION";
+const DOCUMENT_ID: &str = "SPDXRef-DOCUMENT";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: CreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<Relationship>,
+}
+
+#[derive(Serialize)]
+struct CreationInfo {
+    creators: Vec<String>,
+    created: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    name: String,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    version_info: String,
+    download_location: String,
+    files_analyzed: bool,
+    license_concluded: &'static str,
+    license_declared: String,
+    copyright_text: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<Checksum>,
+    external_refs: Vec<ExternalRef>,
+    primary_package_purpose: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Checksum {
+    algorithm: &'static str,
+    checksum_value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+struct Relationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+/// Prints an SPDX 2.3 document describing the packages in the graph, either as tag-value or JSON.
+pub fn print(args: &Args, graph: &Graph, json: bool) -> Result<(), Error> {
+    let packages = tree::find_exported(args, graph)?;
+    let checksums = lockfile::checksums(graph)?;
+
+    let root = match &args.package {
+        Some(_) => Some(tree::find_root(args, graph)?),
+        None => graph.root.as_ref(),
+    };
+
+    let mut ids = HashMap::new();
+    let mut used = BTreeSet::new();
+    for idx in &packages {
+        let package = &graph.graph[*idx];
+        let base = format!("SPDXRef-Package-{}-{}", package.name, package.version)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect::<String>();
+        // the same version of a package may come from several sources
+        let mut id = base.clone();
+        let mut n = 1;
+        while !used.insert(id.clone()) {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        ids.insert(*idx, id);
+    }
+
+    let mut relationships = BTreeSet::new();
+    let described = match root {
+        Some(root) => vec![graph.nodes[root]],
+        None => graph
+            .workspace_members
+            .iter()
+            .filter_map(|id| graph.nodes.get(id).cloned())
+            .filter(|idx| ids.contains_key(idx))
+            .collect(),
+    };
+    for idx in described {
+        relationships.insert(Relationship {
+            spdx_element_id: DOCUMENT_ID.to_string(),
+            relationship_type: "DESCRIBES",
+            related_spdx_element: ids[&idx].clone(),
+        });
+    }
+    for idx in &packages {
+        for edge in graph.graph.edges(*idx) {
+            let (from, to) = (ids[&edge.source()].clone(), ids[&edge.target()].clone());
+            let relationship = match edge.weight() {
+                DependencyKind::Build => Relationship {
+                    spdx_element_id: to,
+                    relationship_type: "BUILD_DEPENDENCY_OF",
+                    related_spdx_element: from,
+                },
+                DependencyKind::Development => Relationship {
+                    spdx_element_id: to,
+                    relationship_type: "DEV_DEPENDENCY_OF",
+                    related_spdx_element: from,
+                },
+                _ => Relationship {
+                    spdx_element_id: from,
+                    relationship_type: "DEPENDS_ON",
+                    related_spdx_element: to,
+                },
+            };
+            relationships.insert(relationship);
+        }
+    }
+
+    let name = match root {
+        Some(root) => {
+            let package = &graph.graph[graph.nodes[root]];
+            format!("{}-{}", package.name, package.version)
+        }
+        None => graph
+            .workspace_root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "workspace".to_string()),
+    };
+
+    let created = now();
+    let document = Document {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: DOCUMENT_ID,
+        document_namespace: format!(
+            "https://spdx.org/spdxdocs/{}-{}",
+            name,
+            created
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect::<String>()
+        ),
+        name,
+        creation_info: CreationInfo {
+            creators: vec![format!(
+                "Tool: {}-{}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )],
+            created,
+        },
+        packages: packages
+            .iter()
+            .map(|idx| {
+                let package = &graph.graph[*idx];
+                spdx_package(package, ids[idx].clone(), checksums.get(&package.id))
+            })
+            .collect(),
+        relationships: relationships.into_iter().collect(),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&document)?);
+    } else {
+        print!("{}", to_tag_value(&document));
+    }
+
+    Ok(())
+}
+
+fn spdx_package(package: &Package, spdx_id: String, checksum: Option<&String>) -> SpdxPackage {
+    let is_bin = package
+        .targets
+        .iter()
+        .any(|t| t.kind.iter().any(|k| k == "bin"));
+
+    SpdxPackage {
+        name: package.name.clone(),
+        spdx_id,
+        version_info: package.version.to_string(),
+        download_location: download_location(package),
+        files_analyzed: false,
+        license_concluded: NOASSERTION,
+        license_declared: match &package.license {
+            Some(license) => license_expression(license),
+            None => NOASSERTION.to_string(),
+        },
+        copyright_text: NOASSERTION,
+        summary: package.description.clone(),
+        checksums: checksum
+            .map(|checksum| Checksum {
+                algorithm: "SHA256",
+                checksum_value: checksum.clone(),
+            })
+            .into_iter()
+            .collect(),
+        external_refs: vec![ExternalRef {
+            reference_category: "PACKAGE-MANAGER",
+            reference_type: "purl",
+            reference_locator: spec::purl(package),
+        }],
+        primary_package_purpose: if is_bin { "APPLICATION" } else { "LIBRARY" },
+    }
+}
+
+fn download_location(package: &Package) -> String {
+    match &package.source {
+        Some(source) if source.is_crates_io() => format!(
+            "https://crates.io/api/v1/crates/{}/{}/download",
+            package.name, package.version
+        ),
+        Some(source) if source.repr.starts_with("git+") => {
+            // SPDX expects `git+https://host/repo@revision`
+            let mut parts = source.repr.splitn(2, '#');
+            let url = parts.next().unwrap().split('?').next().unwrap();
+            match parts.next() {
+                Some(revision) => format!("{}@{}", url, revision),
+                None => url.to_string(),
+            }
+        }
+        Some(_) => NOASSERTION.to_string(),
+        None => "NONE".to_string(),
+    }
+}
+
+fn to_tag_value(document: &Document) -> String {
+    let mut out = String::new();
+    writeln!(out, "SPDXVersion: {}", document.spdx_version).unwrap();
+    writeln!(out, "DataLicense: {}", document.data_license).unwrap();
+    writeln!(out, "SPDXID: {}", document.spdx_id).unwrap();
+    writeln!(out, "DocumentName: {}", document.name).unwrap();
+    writeln!(out, "DocumentNamespace: {}", document.document_namespace).unwrap();
+    for creator in &document.creation_info.creators {
+        writeln!(out, "Creator: {}", creator).unwrap();
+    }
+    writeln!(out, "Created: {}", document.creation_info.created).unwrap();
+
+    for package in &document.packages {
+        writeln!(out).unwrap();
+        writeln!(out, "PackageName: {}", package.name).unwrap();
+        writeln!(out, "SPDXID: {}", package.spdx_id).unwrap();
+        writeln!(out, "PackageVersion: {}", package.version_info).unwrap();
+        writeln!(
+            out,
+            "PackageDownloadLocation: {}",
+            package.download_location
+        )
+        .unwrap();
+        writeln!(out, "FilesAnalyzed: {}", package.files_analyzed).unwrap();
+        for checksum in &package.checksums {
+            writeln!(
+                out,
+                "PackageChecksum: {}: {}",
+                checksum.algorithm, checksum.checksum_value
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "PackageLicenseConcluded: {}",
+            package.license_concluded
+        )
+        .unwrap();
+        writeln!(out, "PackageLicenseDeclared: {}", package.license_declared).unwrap();
+        writeln!(out, "PackageCopyrightText: {}", package.copyright_text).unwrap();
+        if let Some(summary) = &package.summary {
+            writeln!(out, "PackageSummary: <text>{}</text>", summary).unwrap();
+        }
+        for external_ref in &package.external_refs {
+            writeln!(
+                out,
+                "ExternalRef: {} {} {}",
+                external_ref.reference_category,
+                external_ref.reference_type,
+                external_ref.reference_locator
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "PrimaryPackagePurpose: {}",
+            package.primary_package_purpose
+        )
+        .unwrap();
+    }
+
+    writeln!(out).unwrap();
+    for relationship in &document.relationships {
+        writeln!(
+            out,
+            "Relationship: {} {} {}",
+            relationship.spdx_element_id,
+            relationship.relationship_type,
+            relationship.related_spdx_element
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+/// Converts a package's license to an SPDX license expression.
+///
+/// Crates published before SPDX expressions were required may use `/` for `OR`.
+pub fn license_expression(license: &str) -> String {
+    license
+        .split('/')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// Returns the current time in UTC, formatted like `2020-06-01T12:00:00Z`.
+fn now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    timestamp(secs)
+}
+
+/// Formats seconds since the Unix epoch as a UTC timestamp.
+fn timestamp(secs: u64) -> String {
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // converts days since the epoch to a civil date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        let cases = [
+            (0, "1970-01-01T00:00:00Z"),
+            (86_399, "1970-01-01T23:59:59Z"),
+            (951_782_400, "2000-02-29T00:00:00Z"),
+            (951_868_800, "2000-03-01T00:00:00Z"),
+            (1_582_934_400, "2020-02-29T00:00:00Z"),
+            (1_609_459_199, "2020-12-31T23:59:59Z"),
+            (1_609_459_200, "2021-01-01T00:00:00Z"),
+            (4_107_456_000, "2100-02-28T00:00:00Z"),
+            (4_107_542_400, "2100-03-01T00:00:00Z"),
+        ];
+
+        for (secs, expected) in &cases {
+            assert_eq!(timestamp(*secs), *expected, "{}", secs);
+        }
+    }
+
+    #[test]
+    fn license_expressions() {
+        let cases = [
+            ("MIT", "MIT"),
+            ("MIT OR Apache-2.0", "MIT OR Apache-2.0"),
+            ("MIT/Apache-2.0", "MIT OR Apache-2.0"),
+            ("MIT / Apache-2.0", "MIT OR Apache-2.0"),
+            ("Apache-2.0/ISC/MIT", "Apache-2.0 OR ISC OR MIT"),
+        ];
+
+        for (license, expected) in &cases {
+            assert_eq!(license_expression(license), *expected, "{}", license);
+        }
+    }
+}
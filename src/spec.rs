@@ -0,0 +1,343 @@
+use anyhow::{anyhow, Context, Error};
+use cargo_metadata::Package;
+use semver::{Version, VersionReq};
+use std::str::FromStr;
+
+/// A package ID specification, in any of the forms accepted by `cargo pkgid`.
+///
+/// In addition to `name`, `name:version` and `name@version`, the version may be a semver
+/// requirement like `serde@^1`, and the spec may be prefixed with a source URL like
+/// `https://github.com/rust-lang/crates.io-index#serde@1.0.100` or `git+https://...#name`.
+pub struct PackageSpec {
+    name: String,
+    version: Option<VersionSpec>,
+    url: Option<String>,
+}
+
+enum VersionSpec {
+    Exact(Version),
+    Partial(Vec<u64>),
+    Req(VersionReq),
+}
+
+impl VersionSpec {
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionSpec::Exact(v) => version == v,
+            VersionSpec::Partial(parts) => {
+                let actual = [version.major, version.minor, version.patch];
+                parts.iter().zip(&actual).all(|(a, b)| a == b)
+            }
+            VersionSpec::Req(req) => req.matches(version),
+        }
+    }
+}
+
+impl FromStr for VersionSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<VersionSpec, Error> {
+        // semver treats an empty requirement as `*`
+        if s.trim().is_empty() {
+            return Err(anyhow!("missing package version"));
+        }
+
+        if let Ok(version) = Version::parse(s) {
+            return Ok(VersionSpec::Exact(version));
+        }
+
+        if s.split('.')
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+        {
+            let parts = s
+                .split('.')
+                .map(|p| p.parse())
+                .collect::<Result<Vec<u64>, _>>()
+                .context("error parsing package version")?;
+            if parts.len() <= 3 {
+                return Ok(VersionSpec::Partial(parts));
+            }
+        }
+
+        VersionReq::parse(s)
+            .map(VersionSpec::Req)
+            .context("error parsing package version")
+    }
+}
+
+impl FromStr for PackageSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PackageSpec, Error> {
+        if !s.contains("://") {
+            let (name, version) = split_version(s)?;
+            return Ok(PackageSpec {
+                name: name.to_string(),
+                version,
+                url: None,
+            });
+        }
+
+        let mut it = s.splitn(2, '#');
+        let url = it.next().unwrap();
+        let (name, version) = match it.next() {
+            // `url#version` takes the name from the last path segment of the URL
+            Some(fragment) if fragment.starts_with(|c: char| c.is_ascii_digit()) => {
+                (None, Some(fragment.parse()?))
+            }
+            Some(fragment) => {
+                let (name, version) = split_version(fragment)?;
+                (Some(name), version)
+            }
+            None => (None, None),
+        };
+        let name = match name {
+            Some(name) => name,
+            None => url
+                .split_once("://")
+                .map_or("", |(_, path)| path)
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .map(|s| s.trim_end_matches(".git"))
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| anyhow!("unable to infer a package name from `{}`", s))?,
+        };
+
+        Ok(PackageSpec {
+            name: name.to_string(),
+            version,
+            url: Some(url.to_string()),
+        })
+    }
+}
+
+impl PackageSpec {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn matches(&self, package: &Package) -> bool {
+        if package.name != self.name {
+            return false;
+        }
+
+        if let Some(version) = &self.version {
+            if !version.matches(&package.version) {
+                return false;
+            }
+        }
+
+        if let Some(url) = &self.url {
+            if !url_matches(url, &source_url(package)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Returns a spec which unambiguously identifies a package.
+pub fn package_spec(package: &Package) -> String {
+    format!(
+        "{}#{}@{}",
+        source_url(package),
+        package.name,
+        package.version
+    )
+}
+
+/// Returns the package URL (purl) identifying a package in SBOMs and other tooling.
+///
+/// See https://github.com/package-url/purl-spec for the format.
+pub fn purl(package: &Package) -> String {
+    let mut purl = format!(
+        "pkg:cargo/{}@{}",
+        percent_encode(&package.name),
+        percent_encode(&package.version.to_string())
+    );
+
+    match &package.source {
+        Some(source) if source.is_crates_io() => {}
+        Some(source) => {
+            let (kind, url) = split_kind(&source.repr);
+            if kind == Some("git") {
+                purl.push_str(&format!("?vcs_url={}", percent_encode(&source.repr)));
+            } else {
+                purl.push_str(&format!("?repository_url={}", percent_encode(url)));
+            }
+        }
+        None => {}
+    }
+
+    purl
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+fn split_version(s: &str) -> Result<(&str, Option<VersionSpec>), Error> {
+    let mut it = s.splitn(2, &['@', ':'][..]);
+    let name = it.next().unwrap();
+    let version = it.next().map(str::parse).transpose()?;
+    Ok((name, version))
+}
+
+/// Returns the URL of a package's source, without any fragment like a git commit.
+fn source_url(package: &Package) -> String {
+    match &package.source {
+        Some(source) => source.repr.split('#').next().unwrap().to_string(),
+        // https://github.com/rust-lang/cargo/issues/7483
+        None => format!(
+            "path+file://{}",
+            package.manifest_path.parent().unwrap().display()
+        ),
+    }
+}
+
+/// Compares URLs, ignoring the source kind if the spec doesn't specify one.
+fn url_matches(spec: &str, source: &str) -> bool {
+    let (spec_kind, spec_url) = split_kind(spec);
+    let (source_kind, source_url) = split_kind(source);
+
+    if spec_kind.is_some() && spec_kind != source_kind {
+        return false;
+    }
+
+    normalize(spec_url) == normalize(source_url)
+}
+
+fn split_kind(url: &str) -> (Option<&str>, &str) {
+    match url.find('+') {
+        Some(idx) if idx < url.find("://").unwrap_or(0) => (Some(&url[..idx]), &url[idx + 1..]),
+        _ => (None, url),
+    }
+}
+
+fn normalize(url: &str) -> &str {
+    let url = url.split(&['?', '#'][..]).next().unwrap();
+    let url = url.trim_end_matches('/');
+    url.trim_end_matches(".git")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    fn package(name: &str, version: &str, source: Option<&str>) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "version": version,
+            "id": format!("{} {} (path+file:///ws/{})", name, version, name),
+            "source": source,
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": format!("/ws/{}/Cargo.toml", name),
+            "authors": [],
+            "categories": [],
+            "keywords": [],
+            "edition": "2018",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn matches() {
+        let serde = package("serde", "1.0.100", Some(CRATES_IO));
+        let url = package("url", "1.0.0", Some(CRATES_IO));
+        let git = package(
+            "foo",
+            "0.2.0",
+            Some("git+https://github.com/user/foo.git?branch=dev#0123abcd"),
+        );
+        let path = package("bar", "0.1.0", None);
+
+        let cases = [
+            ("serde", &serde, true),
+            ("serd", &serde, false),
+            ("serde:1.0.100", &serde, true),
+            ("serde@1.0.100", &serde, true),
+            ("serde@1.0.101", &serde, false),
+            ("serde@1.0", &serde, true),
+            ("serde@1", &serde, true),
+            ("serde@1.1", &serde, false),
+            ("serde@^1", &serde, true),
+            ("serde@^1.1", &serde, false),
+            ("serde@<1.0.100", &serde, false),
+            (
+                "https://github.com/rust-lang/crates.io-index#serde",
+                &serde,
+                true,
+            ),
+            (
+                "https://github.com/rust-lang/crates.io-index#serde@1.0.100",
+                &serde,
+                true,
+            ),
+            ("https://example.com/index#serde", &serde, false),
+            (
+                "https://github.com/rust-lang/crates.io-index#url@1.0",
+                &url,
+                true,
+            ),
+            ("https://github.com/servo/url#1.0", &url, false),
+            ("https://github.com/servo/url#1.0", &git, false),
+            ("git+https://github.com/user/foo.git#foo@0.2.0", &git, true),
+            ("git+https://github.com/user/foo#0.2", &git, true),
+            ("https://github.com/user/foo", &git, true),
+            ("registry+https://github.com/user/foo#foo", &git, false),
+            ("git+https://github.com/user/foo.git#foo@0.3.0", &git, false),
+            ("bar@0.1", &path, true),
+            ("path+file:///ws/bar#0.1.0", &path, true),
+            ("file:///ws/bar#bar", &path, true),
+            ("file:///ws/baz#bar", &path, false),
+        ];
+
+        for (spec, package, expected) in &cases {
+            let parsed = spec.parse::<PackageSpec>().unwrap();
+            assert_eq!(parsed.matches(package), *expected, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn url_names() {
+        let cases = [
+            ("https://github.com/servo/url#1.0", "url"),
+            ("git+https://github.com/user/foo.git#foo@0.2.0", "foo"),
+            ("git+https://github.com/user/foo.git", "foo"),
+            ("https://github.com/user/foo/#bar", "bar"),
+        ];
+
+        for (spec, name) in &cases {
+            assert_eq!(
+                spec.parse::<PackageSpec>().unwrap().name(),
+                *name,
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = ["serde@", "serde@1.x.y.z", "serde@abc", "https://#1.0"];
+
+        for spec in &cases {
+            assert!(spec.parse::<PackageSpec>().is_err(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::args::Args;
+use crate::format::Pattern;
+use crate::graph::Graph;
+use crate::tree;
+use anyhow::Error;
+use cargo_metadata::DependencyKind;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::EdgeDirection;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub fn print(args: &Args, graph: &Graph) -> Result<(), Error> {
+    let format = Pattern::new(&args.format)?;
+
+    println!("packages: {}", graph.graph.node_count());
+
+    let mut normal = 0;
+    let mut build = 0;
+    let mut development = 0;
+    for edge in graph.graph.edge_indices() {
+        match graph.graph[edge] {
+            DependencyKind::Normal => normal += 1,
+            DependencyKind::Build => build += 1,
+            DependencyKind::Development => development += 1,
+            _ => {}
+        }
+    }
+    println!(
+        "edges: {} (normal: {}, build: {}, dev: {})",
+        graph.graph.edge_count(),
+        normal,
+        build,
+        development
+    );
+
+    let depths = depths(args, graph)?;
+    let max = depths.values().max().cloned().unwrap_or(0);
+    let mean = if depths.is_empty() {
+        0.
+    } else {
+        depths.values().sum::<usize>() as f64 / depths.len() as f64
+    };
+    println!("depth: max {}, mean {:.2}", max, mean);
+
+    let mut names = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        *names.entry(&graph.graph[idx].name).or_insert(0) += 1;
+    }
+    let duplicates = names.values().filter(|c| **c > 1).count();
+    println!("duplicated names: {}", duplicates);
+
+    let mut registry = 0;
+    let mut git = 0;
+    let mut path = 0;
+    for idx in graph.graph.node_indices() {
+        match &graph.graph[idx].source {
+            Some(source) if source.repr.starts_with("git+") => git += 1,
+            Some(_) => registry += 1,
+            None => path += 1,
+        }
+    }
+    println!("sources: {} registry, {} git, {} path", registry, git, path);
+
+    for (title, direction) in &[
+        ("most depended on (fan-in)", EdgeDirection::Incoming),
+        ("most dependencies (fan-out)", EdgeDirection::Outgoing),
+    ] {
+        let mut counts = graph
+            .graph
+            .node_indices()
+            .map(|idx| (neighbors(graph, idx, *direction), idx))
+            .filter(|(count, _)| *count > 0)
+            .collect::<Vec<_>>();
+        counts.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| graph.graph[a.1].id.cmp(&graph.graph[b.1].id))
+        });
+
+        println!();
+        println!("{}:", title);
+        for (count, idx) in counts.iter().take(args.top) {
+            println!("{:>6} {}", count, format.display(&graph.graph[*idx]));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the shortest distance of each package from the root package, or from the workspace
+/// members if there is no root.
+fn depths(args: &Args, graph: &Graph) -> Result<HashMap<NodeIndex, usize>, Error> {
+    let roots = if args.package.is_some() || graph.root.is_some() {
+        vec![graph.nodes[tree::find_root(args, graph)?]]
+    } else {
+        graph
+            .workspace_members
+            .iter()
+            .filter_map(|id| graph.nodes.get(id).cloned())
+            .collect()
+    };
+
+    let mut depths = HashMap::new();
+    let mut queue = VecDeque::new();
+    for root in roots {
+        depths.insert(root, 0);
+        queue.push_back(root);
+    }
+
+    while let Some(idx) = queue.pop_front() {
+        let depth = depths[&idx];
+        for edge in graph.graph.edges(idx) {
+            if let Entry::Vacant(entry) = depths.entry(edge.target()) {
+                entry.insert(depth + 1);
+                queue.push_back(edge.target());
+            }
+        }
+    }
+
+    Ok(depths)
+}
+
+/// Returns the number of distinct packages connected to a package in a direction.
+fn neighbors(graph: &Graph, idx: NodeIndex, direction: EdgeDirection) -> usize {
+    graph
+        .graph
+        .neighbors_directed(idx, direction)
+        .collect::<HashSet<_>>()
+        .len()
+}
@@ -0,0 +1,147 @@
+use crate::args::{Args, Rows};
+use crate::format::Pattern;
+use crate::graph::{kind_name, Graph};
+use crate::tree;
+use anyhow::Error;
+use cargo_metadata::DependencyKind;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::EdgeDirection;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+enum Column {
+    Pattern(Pattern),
+    Depth,
+    Parent,
+    Kind,
+}
+
+/// A package reached from the root, along with how it was reached.
+struct Row {
+    package: NodeIndex,
+    depth: usize,
+    parent: Option<(NodeIndex, DependencyKind)>,
+}
+
+/// Prints the tree as a table with one row per package or per edge, with fields separated by
+/// `separator`.
+pub fn print(args: &Args, graph: &Graph, separator: char) -> Result<(), Error> {
+    let parent_format = Pattern::new(&args.format)?;
+    let mut columns = vec![];
+    let mut names = vec![];
+    for name in args.columns.split(',').map(str::trim) {
+        let column = match name {
+            "depth" => Column::Depth,
+            "parent" => Column::Parent,
+            "kind" => Column::Kind,
+            _ => Column::Pattern(Pattern::new(&format!("{{{}}}", name))?),
+        };
+        columns.push(column);
+        names.push(name.to_string());
+    }
+
+    let direction = if args.invert {
+        EdgeDirection::Incoming
+    } else {
+        EdgeDirection::Outgoing
+    };
+
+    let roots = if args.package.is_some() || graph.root.is_some() {
+        vec![graph.nodes[tree::find_root(args, graph)?]]
+    } else {
+        graph
+            .workspace_members
+            .iter()
+            .filter_map(|id| graph.nodes.get(id).cloned())
+            .collect()
+    };
+
+    let mut rows = vec![];
+    let mut depths = HashMap::new();
+    let mut queue = VecDeque::new();
+    for root in roots {
+        depths.insert(root, 0);
+        queue.push_back(root);
+        rows.push(Row {
+            package: root,
+            depth: 0,
+            parent: None,
+        });
+    }
+
+    while let Some(idx) = queue.pop_front() {
+        let depth = depths[&idx];
+
+        let mut edges = graph
+            .graph
+            .edges_directed(idx, direction)
+            .map(|edge| match direction {
+                EdgeDirection::Incoming => (edge.source(), *edge.weight()),
+                EdgeDirection::Outgoing => (edge.target(), *edge.weight()),
+            })
+            .collect::<Vec<_>>();
+        // ensure a consistent output ordering
+        edges.sort_by_key(|(idx, kind)| (&graph.graph[*idx].id, kind_name(*kind)));
+
+        for (child, kind) in edges {
+            let new = match depths.entry(child) {
+                Entry::Vacant(entry) => {
+                    entry.insert(depth + 1);
+                    queue.push_back(child);
+                    true
+                }
+                Entry::Occupied(_) => false,
+            };
+
+            if new || matches!(args.rows, Rows::Edges) {
+                rows.push(Row {
+                    package: child,
+                    depth: depth + 1,
+                    parent: Some((idx, kind)),
+                });
+            }
+        }
+    }
+
+    print_record(&names, separator);
+    for row in &rows {
+        let fields = columns
+            .iter()
+            .map(|column| match column {
+                Column::Pattern(pattern) => pattern.display(&graph.graph[row.package]).to_string(),
+                Column::Depth => row.depth.to_string(),
+                Column::Parent => row
+                    .parent
+                    .map(|(idx, _)| parent_format.display(&graph.graph[idx]).to_string())
+                    .unwrap_or_default(),
+                Column::Kind => row
+                    .parent
+                    .map(|(_, kind)| kind_name(kind).to_string())
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        print_record(&fields, separator);
+    }
+
+    Ok(())
+}
+
+fn print_record(fields: &[String], separator: char) {
+    let fields = fields
+        .iter()
+        .map(|field| escape(field, separator))
+        .collect::<Vec<_>>();
+    println!("{}", fields.join(&separator.to_string()));
+}
+
+/// Quotes a CSV field if needed, or replaces the characters TSV can't represent.
+fn escape(field: &str, separator: char) -> String {
+    if separator == '\t' {
+        field.replace(['\t', '\n', '\r'], " ")
+    } else if field.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,157 @@
+use crate::args::Args;
+use crate::format::Pattern;
+use crate::graph::Graph;
+use crate::tree;
+use anyhow::{anyhow, Error};
+use cargo_metadata::DependencyKind;
+use petgraph::algo;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::{EdgeFiltered, EdgeRef};
+use std::collections::{HashMap, HashSet};
+
+/// Prints the packages in an order in which they could be built, dependencies first, grouped into
+/// levels whose packages only depend on those in earlier levels.
+///
+/// Dev-dependencies are ignored since they're only needed to build a package's tests, not the
+/// package itself, and they are the only way the graph can contain cycles.
+pub fn print(args: &Args, graph: &Graph) -> Result<(), Error> {
+    let format = Pattern::new(&args.format)?;
+    let packages = tree::find_exported(args, graph)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    let mut grouped = vec![];
+    for (idx, level) in levels(graph)? {
+        if !packages.contains(&idx) {
+            continue;
+        }
+        if grouped.len() <= level {
+            grouped.resize_with(level + 1, Vec::new);
+        }
+        grouped[level].push(idx);
+    }
+
+    for (i, level) in grouped.iter_mut().enumerate() {
+        level.sort_by_key(|idx| &graph.graph[*idx].id);
+
+        if i != 0 {
+            println!();
+        }
+        let s = if level.len() == 1 { "" } else { "s" };
+        println!("level {} ({} package{}):", i, level.len(), s);
+        for idx in level {
+            println!("    {}", format.display(&graph.graph[*idx]));
+        }
+    }
+
+    println!();
+    println!(
+        "{} packages in {} levels, at most {} in one level",
+        packages.len(),
+        grouped.len(),
+        grouped.iter().map(Vec::len).max().unwrap_or(0)
+    );
+
+    Ok(())
+}
+
+/// Returns the level of each package, which is one more than the highest level of the packages it
+/// needs to build, so packages without dependencies are at level 0.
+fn levels(graph: &Graph) -> Result<HashMap<NodeIndex, usize>, Error> {
+    let mut levels = HashMap::new();
+    for idx in build_order(graph)? {
+        let level = graph
+            .graph
+            .edges(idx)
+            .filter(|edge| *edge.weight() != DependencyKind::Development)
+            .map(|edge| levels[&edge.target()] + 1)
+            .max()
+            .unwrap_or(0);
+        levels.insert(idx, level);
+    }
+
+    Ok(levels)
+}
+
+/// Returns the packages in the graph ordered so that each comes after everything it needs to
+/// build, ignoring dev-dependencies.
+pub fn build_order(graph: &Graph) -> Result<Vec<NodeIndex>, Error> {
+    let build_graph = EdgeFiltered::from_fn(&graph.graph, |edge| {
+        *edge.weight() != DependencyKind::Development
+    });
+    let mut order = algo::toposort(&build_graph, None).map_err(|cycle| {
+        anyhow!(
+            "the dependency graph contains a cycle through `{}`",
+            graph.graph[cycle.node_id()].name
+        )
+    })?;
+
+    // the sort puts dependents first
+    order.reverse();
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::{graph, node, package};
+    use cargo_metadata::DependencyKind::{Build, Development, Normal};
+
+    fn packages(names: &[&str]) -> Vec<cargo_metadata::Package> {
+        names
+            .iter()
+            .map(|name| package(name, serde_json::json!({})))
+            .collect()
+    }
+
+    #[test]
+    fn order() {
+        // the root's tests depend on b, which depends on the root
+        let graph = graph(
+            packages(&["root", "a", "b", "c", "d"]),
+            &[
+                ("root", "a", Normal),
+                ("root", "b", Development),
+                ("root", "d", Build),
+                ("a", "c", Normal),
+                ("b", "root", Normal),
+                ("d", "c", Normal),
+            ],
+        );
+
+        let order = build_order(&graph).unwrap();
+        assert_eq!(order.len(), 5);
+        let position = |name| order.iter().position(|idx| *idx == node(&graph, name));
+        for (dependent, dependency) in &[
+            ("root", "a"),
+            ("root", "d"),
+            ("a", "c"),
+            ("d", "c"),
+            ("b", "root"),
+        ] {
+            assert!(
+                position(dependency) < position(dependent),
+                "{} before {}",
+                dependency,
+                dependent
+            );
+        }
+
+        let levels = levels(&graph).unwrap();
+        let level = |name| levels[&node(&graph, name)];
+        assert_eq!(level("c"), 0);
+        assert_eq!(level("a"), 1);
+        assert_eq!(level("d"), 1);
+        assert_eq!(level("root"), 2);
+        assert_eq!(level("b"), 3);
+    }
+
+    #[test]
+    fn cycle() {
+        let graph = graph(
+            packages(&["root", "a", "b"]),
+            &[("root", "a", Normal), ("a", "b", Normal), ("b", "a", Build)],
+        );
+        assert!(build_order(&graph).is_err());
+    }
+}
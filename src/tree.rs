@@ -1,35 +1,46 @@
-use crate::args::{Args, Charset};
+use crate::advisories;
+use crate::args::{Args, Charset, ColorChoice};
 use crate::format::Pattern;
-use crate::graph::Graph;
-use anyhow::{anyhow, Context, Error};
+use crate::graph::{self, Exclusion, Graph};
+use crate::outdated;
+use crate::size;
+use crate::spec::{self, PackageSpec};
+use crate::unsafe_code;
+use anyhow::{anyhow, Error};
 use cargo_metadata::{DependencyKind, Package, PackageId};
-use petgraph::visit::EdgeRef;
+use crossterm::style::{Attribute, Color, ContentStyle};
+use crossterm::tty::IsTty;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::{Dfs, EdgeRef, Reversed};
 use petgraph::EdgeDirection;
-use semver::Version;
 use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::io;
 
 #[derive(Clone, Copy)]
 enum Prefix {
     None,
     Indent,
     Depth,
+    Paths,
 }
 
-struct Symbols {
-    down: &'static str,
-    tee: &'static str,
-    ell: &'static str,
-    right: &'static str,
+pub struct Symbols {
+    pub down: &'static str,
+    pub tee: &'static str,
+    pub ell: &'static str,
+    pub right: &'static str,
 }
 
-static UTF8_SYMBOLS: Symbols = Symbols {
+pub static UTF8_SYMBOLS: Symbols = Symbols {
     down: "│",
     tee: "├",
     ell: "└",
     right: "─",
 };
 
-static ASCII_SYMBOLS: Symbols = Symbols {
+pub static ASCII_SYMBOLS: Symbols = Symbols {
     down: "|",
     tee: "|",
     ell: "`",
@@ -50,7 +61,9 @@ pub fn print(args: &Args, graph: &Graph) -> Result<(), Error> {
         Charset::Ascii => &ASCII_SYMBOLS,
     };
 
-    let prefix = if args.prefix_depth {
+    let prefix = if args.paths {
+        Prefix::Paths
+    } else if args.prefix_depth {
         Prefix::Depth
     } else if args.no_indent {
         Prefix::None
@@ -58,61 +71,214 @@ pub fn print(args: &Args, graph: &Graph) -> Result<(), Error> {
         Prefix::Indent
     };
 
+    let duplicates = find_duplicates(graph);
+    let (highlighted, ancestors) = find_highlighted(args, graph, direction)?;
+
+    // the package which per-package information like paths and exclusive sizes is relative to
+    let root = match &args.package {
+        Some(_) => Some(find_root(args, graph)?),
+        None => graph.root.as_ref(),
+    };
+    let root = root.map(|id| graph.nodes[id]);
+
+    let advisories = match &args.advisories {
+        Some(path) => advisories::find(args, path, graph, root)?,
+        None => vec![],
+    };
+
+    let outdated = if args.outdated {
+        outdated::find(graph)?
+    } else {
+        HashMap::new()
+    };
+
+    // only the tree shows the host and target builds of packages separately
+    let split = graph::split_platforms(args, graph);
+    let printer = Printer {
+        graph: split.as_ref().unwrap_or(graph),
+        format: &format,
+        direction,
+        symbols,
+        prefix,
+        // every chain leading to a package gets its own line, rather than only the first
+        all: args.all || args.paths,
+        depth: args.depth,
+        color: use_color(args),
+        duplicates: duplicates
+            .iter()
+            .map(|id| &*graph.graph[graph.nodes[*id]].name)
+            .collect(),
+        highlighted,
+        ancestors,
+        annotations: annotations(args, graph, root, &advisories, &outdated)?,
+        outdated: outdated
+            .iter()
+            .filter(|(_, status)| status.lags())
+            .map(|(idx, _)| &graph.graph[*idx].id)
+            .collect(),
+    };
+
     if args.duplicates {
-        for (i, package) in find_duplicates(graph).iter().enumerate() {
+        for (i, package) in duplicates.iter().enumerate() {
             if i != 0 {
                 println!();
             }
 
-            let root = &graph.graph[graph.nodes[*package]];
-            print_tree(graph, root, &format, direction, symbols, prefix, args.all);
+            printer.print_tree(package);
         }
     } else {
-        let root = match &args.package {
-            Some(package) => find_package(package, graph)?,
-            None => graph.root.as_ref().ok_or_else(|| {
-                anyhow!("this command requires running against an actual package in this workspace")
-            })?,
-        };
-        let root = &graph.graph[graph.nodes[root]];
+        printer.print_tree(find_root(args, graph)?);
+    }
+
+    print_advisories(graph, &advisories)
+}
+
+/// Prints the details of each advisory affecting the graph, failing if any are vulnerabilities.
+fn print_advisories(graph: &Graph, matches: &[advisories::Match]) -> Result<(), Error> {
+    let display = |idx: NodeIndex| {
+        let package = &graph.graph[idx];
+        format!("{} v{}", package.name, package.version)
+    };
 
-        print_tree(graph, root, &format, direction, symbols, prefix, args.all);
+    for m in matches {
+        let advisory = &m.advisory;
+        println!();
+        println!("{}: {}", advisory.id, advisory.title);
+        println!("  package: {}", display(m.package));
+        if let Some(informational) = &advisory.informational {
+            println!("  informational: {}", informational);
+        }
+        if advisory.patched.is_empty() {
+            println!("  patched: no patched versions");
+        } else {
+            println!("  patched: {}", advisory.patched.join(", "));
+        }
+        if let Some(url) = &advisory.url {
+            println!("  url: {}", url);
+        }
+        if !m.path.is_empty() {
+            let path = m.path.iter().map(|idx| display(*idx)).collect::<Vec<_>>();
+            println!("  path: {}", path.join(" > "));
+        }
+    }
+
+    let vulnerabilities = matches
+        .iter()
+        .filter(|m| m.advisory.informational.is_none())
+        .count();
+    if vulnerabilities > 0 {
+        return Err(anyhow!(
+            "{} vulnerabilit{} found",
+            vulnerabilities,
+            if vulnerabilities == 1 { "y" } else { "ies" }
+        ));
     }
 
     Ok(())
 }
 
-fn find_package<'a>(package: &str, graph: &'a Graph) -> Result<&'a PackageId, Error> {
-    let mut it = package.split(':');
-    let name = it.next().unwrap();
-    let version = it
-        .next()
-        .map(Version::parse)
-        .transpose()
-        .context("error parsing package version")?;
-
-    let mut candidates = vec![];
-    for idx in graph.graph.node_indices() {
-        let package = &graph.graph[idx];
-        if package.name != name {
-            continue;
+/// Collects the extra information requested for each package, like `--size` and `--unsafe`, and
+/// the advisories affecting it.
+fn annotations<'a>(
+    args: &Args,
+    graph: &'a Graph,
+    root: Option<NodeIndex>,
+    advisories: &[advisories::Match],
+    outdated: &HashMap<NodeIndex, outdated::Status>,
+) -> Result<HashMap<&'a PackageId, Vec<String>>, Error> {
+    let mut annotations = HashMap::new();
+    if args.size {
+        for (id, annotation) in size::annotations(graph, root)? {
+            annotations
+                .entry(id)
+                .or_insert_with(Vec::new)
+                .push(annotation);
+        }
+    }
+    if args.unsafe_code {
+        for (id, annotation) in unsafe_code::annotations(graph)? {
+            annotations
+                .entry(id)
+                .or_insert_with(Vec::new)
+                .push(annotation);
+        }
+    }
+    for m in advisories {
+        let mut annotation = m.advisory.id.clone();
+        if let Some(informational) = &m.advisory.informational {
+            annotation.push_str(&format!(" ({})", informational));
         }
+        annotations
+            .entry(&graph.graph[m.package].id)
+            .or_insert_with(Vec::new)
+            .push(annotation);
+    }
+    // packages which are up to date aren't worth pointing out
+    for (idx, status) in outdated.iter().filter(|(_, status)| status.lags()) {
+        annotations
+            .entry(&graph.graph[*idx].id)
+            .or_insert_with(Vec::new)
+            .push(status.to_string());
+    }
 
-        if let Some(version) = &version {
-            if package.version != *version {
-                continue;
+    Ok(annotations)
+}
+
+/// Determines if the tree should be colored, based on `--color`, `CARGO_TERM_COLOR` and `NO_COLOR`.
+fn use_color(args: &Args) -> bool {
+    let choice = match args.color {
+        Some(choice) => choice,
+        None => env::var("CARGO_TERM_COLOR")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(ColorChoice::Auto),
+    };
+
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => env::var_os("NO_COLOR").is_none() && io::stdout().is_tty(),
+    }
+}
+
+/// Returns the package selected with `-p`, or the workspace's root package.
+pub fn find_root<'a>(args: &Args, graph: &'a Graph) -> Result<&'a PackageId, Error> {
+    match &args.package {
+        Some(package) => find_package(package, graph),
+        None => graph.root.as_ref().ok_or_else(|| {
+            anyhow!("this command requires running against an actual package in this workspace")
+        }),
+    }
+}
+
+/// Returns the packages in the graph which belong in an export like an SBOM, in a stable order.
+///
+/// With `-p` this is the selected package and everything it depends on, and otherwise it is
+/// every package in the graph.
+pub fn find_exported(args: &Args, graph: &Graph) -> Result<Vec<NodeIndex>, Error> {
+    let mut packages = match &args.package {
+        Some(_) => {
+            let mut dfs = Dfs::new(&graph.graph, graph.nodes[find_root(args, graph)?]);
+            let mut packages = vec![];
+            while let Some(idx) = dfs.next(&graph.graph) {
+                packages.push(idx);
             }
+            packages
         }
+        None => graph.graph.node_indices().collect(),
+    };
+    packages.sort_by_key(|idx| &graph.graph[*idx].id);
 
-        candidates.push(package);
-    }
+    Ok(packages)
+}
 
-    if candidates.is_empty() {
-        Err(anyhow!("no crates found for package `{}`", package))
-    } else if candidates.len() > 1 {
+pub fn find_package<'a>(package: &str, graph: &'a Graph) -> Result<&'a PackageId, Error> {
+    let candidates = find_packages(package, graph)?;
+
+    if candidates.len() > 1 {
         let specs = candidates
             .iter()
-            .map(|p| format!("{}:{}", p.name, p.version))
+            .map(|p| spec::package_spec(p))
             .collect::<Vec<_>>()
             .join(", ");
         Err(anyhow!(
@@ -125,7 +291,119 @@ fn find_package<'a>(package: &str, graph: &'a Graph) -> Result<&'a PackageId, Er
     }
 }
 
-fn find_duplicates(graph: &Graph) -> Vec<&PackageId> {
+/// Returns every package matching a spec, which must match at least one.
+fn find_packages<'a>(package: &str, graph: &'a Graph) -> Result<Vec<&'a Package>, Error> {
+    let spec = package.parse::<PackageSpec>()?;
+
+    let mut candidates = vec![];
+    for idx in graph.graph.node_indices() {
+        let package = &graph.graph[idx];
+        if !spec.matches(package) {
+            continue;
+        }
+
+        candidates.push(package);
+    }
+
+    if candidates.is_empty() {
+        let mut message = format!("no crates found for package `{}`", package);
+
+        for (excluded, exclusion) in graph.excluded()? {
+            if !spec.matches(excluded) {
+                continue;
+            }
+
+            let reason = match exclusion {
+                Exclusion::Target => {
+                    "it is only used by other targets (see --target and --all-targets)"
+                }
+                Exclusion::DevDependency => {
+                    "it is only used as a dev-dependency (see --no-dev-dependencies)"
+                }
+                Exclusion::Unreachable => "it is not a dependency of the root package",
+            };
+            message.push_str(&format!(
+                "\n\n`{} v{}` is not in the graph because {}",
+                excluded.name, excluded.version, reason
+            ));
+        }
+
+        let suggestions = suggest_names(spec.name(), graph);
+        if !suggestions.is_empty() {
+            let suggestions = suggestions
+                .iter()
+                .map(|s| format!("`{}`", s))
+                .collect::<Vec<_>>()
+                .join(", ");
+            message.push_str(&format!("\n\nDid you mean {}?", suggestions));
+        }
+
+        Err(anyhow!("{}", message))
+    } else {
+        Ok(candidates)
+    }
+}
+
+/// Returns the names of packages in the graph which are similar to or extend a name.
+fn suggest_names<'a>(name: &str, graph: &'a Graph) -> Vec<&'a str> {
+    let mut suggestions = vec![];
+
+    for idx in graph.graph.node_indices() {
+        let candidate = &*graph.graph[idx].name;
+        if candidate == name {
+            continue;
+        }
+
+        let distance = strsim::levenshtein(name, candidate);
+        if distance <= (name.len() / 3).max(1) || candidate.starts_with(name) {
+            suggestions.push((distance, candidate));
+        }
+    }
+
+    suggestions.sort();
+    suggestions.dedup();
+    suggestions.into_iter().take(5).map(|(_, s)| s).collect()
+}
+
+/// Returns the packages selected by `--highlight`, along with the packages leading to them in the
+/// tree if `--highlight-ancestors` was passed.
+fn find_highlighted<'a>(
+    args: &Args,
+    graph: &'a Graph,
+    direction: EdgeDirection,
+) -> Result<(HashSet<&'a PackageId>, HashSet<&'a PackageId>), Error> {
+    let mut highlighted = HashSet::new();
+    for spec in &args.highlight {
+        highlighted.extend(find_packages(spec, graph)?.into_iter().map(|p| &p.id));
+    }
+
+    let mut ancestors = HashSet::new();
+    if args.highlight_ancestors {
+        // ancestors in the tree are found by walking the graph against the tree's direction
+        let reversed = Reversed(&graph.graph);
+        for id in &highlighted {
+            let start = graph.nodes[*id];
+            match direction {
+                EdgeDirection::Outgoing => {
+                    let mut dfs = Dfs::new(reversed, start);
+                    while let Some(idx) = dfs.next(reversed) {
+                        ancestors.insert(&graph.graph[idx].id);
+                    }
+                }
+                EdgeDirection::Incoming => {
+                    let mut dfs = Dfs::new(&graph.graph, start);
+                    while let Some(idx) = dfs.next(&graph.graph) {
+                        ancestors.insert(&graph.graph[idx].id);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((highlighted, ancestors))
+}
+
+pub fn find_duplicates(graph: &Graph) -> Vec<&PackageId> {
     let mut packages = HashMap::new();
 
     for idx in graph.graph.node_indices() {
@@ -147,156 +425,257 @@ fn find_duplicates(graph: &Graph) -> Vec<&PackageId> {
     duplicates
 }
 
-fn print_tree<'a>(
+struct Printer<'a> {
     graph: &'a Graph,
-    root: &'a Package,
-    format: &Pattern,
+    format: &'a Pattern,
     direction: EdgeDirection,
-    symbols: &Symbols,
+    symbols: &'a Symbols,
     prefix: Prefix,
     all: bool,
-) {
-    let mut visited_deps = HashSet::new();
-    let mut levels_continue = vec![];
-
-    print_package(
-        graph,
-        root,
-        format,
-        direction,
-        symbols,
-        prefix,
-        all,
-        &mut visited_deps,
-        &mut levels_continue,
-    );
+    depth: Option<usize>,
+    color: bool,
+    duplicates: HashSet<&'a str>,
+    highlighted: HashSet<&'a PackageId>,
+    ancestors: HashSet<&'a PackageId>,
+    annotations: HashMap<&'a PackageId, Vec<String>>,
+    /// Packages which are yanked or have newer versions available.
+    outdated: HashSet<&'a PackageId>,
 }
 
-fn print_package<'a>(
-    graph: &'a Graph,
-    package: &'a Package,
-    format: &Pattern,
-    direction: EdgeDirection,
-    symbols: &Symbols,
-    prefix: Prefix,
-    all: bool,
-    visited_deps: &mut HashSet<&'a PackageId>,
-    levels_continue: &mut Vec<bool>,
-) {
-    let new = all || visited_deps.insert(&package.id);
-
-    match prefix {
-        Prefix::Depth => print!("{}", levels_continue.len()),
-        Prefix::Indent => {
-            if let Some((last_continues, rest)) = levels_continue.split_last() {
-                for continues in rest {
-                    let c = if *continues { symbols.down } else { " " };
-                    print!("{}   ", c);
-                }
-
-                let c = if *last_continues {
-                    symbols.tee
-                } else {
-                    symbols.ell
-                };
-                print!("{0}{1}{1} ", c, symbols.right);
+impl<'a> Printer<'a> {
+    /// Prints the tree below a package, once for each of its builds if it's built separately for
+    /// the host and the target.
+    fn print_tree(&self, root: &PackageId) {
+        for (i, idx) in self.graph.package_nodes(root).into_iter().enumerate() {
+            if i != 0 {
+                println!();
             }
+
+            let mut visited_deps = HashSet::new();
+            let mut levels_continue = vec![];
+            let mut path = vec![];
+
+            self.print_package(idx, &mut visited_deps, &mut levels_continue, &mut path);
         }
-        Prefix::None => {}
     }
 
-    let star = if new { "" } else { " (*)" };
-    println!("{}{}", format.display(package), star);
+    fn print_package(
+        &self,
+        idx: NodeIndex,
+        visited_deps: &mut HashSet<NodeIndex>,
+        levels_continue: &mut Vec<bool>,
+        path: &mut Vec<NodeIndex>,
+    ) {
+        let package = &self.graph.graph[idx];
+        // even with --all, a package already on the path is part of a cycle through
+        // dev-dependencies, which would otherwise be followed forever
+        let new = if self.all {
+            !path.contains(&idx)
+        } else {
+            visited_deps.insert(idx)
+        };
 
-    if !new {
-        return;
-    }
+        match self.prefix {
+            Prefix::Depth => print!("{}", levels_continue.len()),
+            Prefix::Indent => {
+                if let Some((last_continues, rest)) = levels_continue.split_last() {
+                    for continues in rest {
+                        let c = if *continues { self.symbols.down } else { " " };
+                        print!("{}   ", c);
+                    }
+
+                    let c = if *last_continues {
+                        self.symbols.tee
+                    } else {
+                        self.symbols.ell
+                    };
+                    print!("{0}{1}{1} ", c, self.symbols.right);
+                }
+            }
+            Prefix::Paths => {
+                for ancestor in &**path {
+                    print!("{} > ", self.format.display(&self.graph.graph[*ancestor]));
+                }
+            }
+            Prefix::None => {}
+        }
 
-    for kind in &[
-        DependencyKind::Normal,
-        DependencyKind::Build,
-        DependencyKind::Development,
-    ] {
-        print_dependencies(
-            graph,
-            package,
-            format,
-            direction,
-            symbols,
-            prefix,
-            all,
-            visited_deps,
-            levels_continue,
-            *kind,
-        );
+        let (style, marker) = if self.highlighted.contains(&package.id) {
+            let style = ContentStyle::new()
+                .foreground(Color::Red)
+                .attribute(Attribute::Bold)
+                .attribute(Attribute::Reverse);
+            (style, " <==")
+        } else if self.ancestors.contains(&package.id) {
+            let style = self.package_style(package).attribute(Attribute::Underlined);
+            (style, " <--")
+        } else {
+            (self.package_style(package), "")
+        };
+        print!("{}", self.paint(self.format.display(package), style));
+        if let Some(platform) = self.graph.platform(idx) {
+            print!(" ({})", platform);
+        }
+        if let Some(targets) = self.graph.partial_targets(&package.id) {
+            print!(" [{}]", targets.join(", "));
+        }
+        for annotation in self.annotations.get(&package.id).into_iter().flatten() {
+            print!(" [{}]", annotation);
+        }
+        if !self.color {
+            print!("{}", marker);
+        }
+        if !new {
+            let style = ContentStyle::new().attribute(Attribute::Dim);
+            print!(" {}", self.paint("(*)", style));
+        }
+        println!();
+
+        if !new
+            || self
+                .depth
+                .is_some_and(|depth| levels_continue.len() >= depth)
+        {
+            return;
+        }
+
+        for kind in &[
+            DependencyKind::Normal,
+            DependencyKind::Build,
+            DependencyKind::Development,
+        ] {
+            path.push(idx);
+            self.print_dependencies(idx, visited_deps, levels_continue, path, *kind);
+            path.pop();
+        }
     }
-}
 
-fn print_dependencies<'a>(
-    graph: &'a Graph,
-    package: &'a Package,
-    format: &Pattern,
-    direction: EdgeDirection,
-    symbols: &Symbols,
-    prefix: Prefix,
-    all: bool,
-    visited_deps: &mut HashSet<&'a PackageId>,
-    levels_continue: &mut Vec<bool>,
-    kind: DependencyKind,
-) {
-    let idx = graph.nodes[&package.id];
-    let mut deps = vec![];
-    for edge in graph.graph.edges_directed(idx, direction) {
-        if *edge.weight() != kind {
-            continue;
+    fn print_dependencies(
+        &self,
+        idx: NodeIndex,
+        visited_deps: &mut HashSet<NodeIndex>,
+        levels_continue: &mut Vec<bool>,
+        path: &mut Vec<NodeIndex>,
+        kind: DependencyKind,
+    ) {
+        let mut deps = vec![];
+        for edge in self.graph.graph.edges_directed(idx, self.direction) {
+            if *edge.weight() != kind {
+                continue;
+            }
+
+            let dep = match self.direction {
+                EdgeDirection::Incoming => edge.source(),
+                EdgeDirection::Outgoing => edge.target(),
+            };
+            deps.push(dep);
         }
 
-        let dep = match direction {
-            EdgeDirection::Incoming => &graph.graph[edge.source()],
-            EdgeDirection::Outgoing => &graph.graph[edge.target()],
+        if deps.is_empty() {
+            return;
+        }
+
+        // ensure a consistent output ordering
+        deps.sort_by_key(|idx| (&self.graph.graph[*idx].id, self.graph.platform(*idx)));
+
+        let name = match kind {
+            DependencyKind::Normal => None,
+            DependencyKind::Build => Some(("[build-dependencies]", Color::Blue)),
+            DependencyKind::Development => Some(("[dev-dependencies]", Color::DarkMagenta)),
+            _ => unreachable!(),
         };
-        deps.push(dep);
-    }
 
-    if deps.is_empty() {
-        return;
-    }
+        if let Prefix::Indent = self.prefix {
+            if let Some((name, color)) = name {
+                for continues in &**levels_continue {
+                    let c = if *continues { self.symbols.down } else { " " };
+                    print!("{}   ", c);
+                }
 
-    // ensure a consistent output ordering
-    deps.sort_by_key(|p| &p.id);
+                println!(
+                    "{}",
+                    self.paint(name, ContentStyle::new().foreground(color))
+                );
+            }
+        }
 
-    let name = match kind {
-        DependencyKind::Normal => None,
-        DependencyKind::Build => Some("[build-dependencies]"),
-        DependencyKind::Development => Some("[dev-dependencies]"),
-        _ => unreachable!(),
-    };
+        let mut it = deps.iter().peekable();
+        while let Some(dependency) = it.next() {
+            levels_continue.push(it.peek().is_some());
+            self.print_package(*dependency, visited_deps, levels_continue, path);
+            levels_continue.pop();
+        }
+    }
 
-    if let Prefix::Indent = prefix {
-        if let Some(name) = name {
-            for continues in &**levels_continue {
-                let c = if *continues { symbols.down } else { " " };
-                print!("{}   ", c);
+    /// Workspace members are highlighted, followed by packages with multiple versions in the
+    /// graph, then git and path dependencies.
+    fn package_style(&self, package: &Package) -> ContentStyle {
+        let style = ContentStyle::new();
+
+        if self.outdated.contains(&package.id) {
+            style.foreground(Color::Red)
+        } else if self.graph.workspace_members.contains(&package.id) {
+            style.foreground(Color::Green).attribute(Attribute::Bold)
+        } else if self.duplicates.contains(&*package.name) {
+            style.foreground(Color::Yellow)
+        } else {
+            match &package.source {
+                Some(source) if source.repr.starts_with("git+") => style.foreground(Color::Magenta),
+                None => style.foreground(Color::Cyan),
+                _ => style,
             }
+        }
+    }
 
-            println!("{}", name);
+    fn paint<D>(&self, content: D, style: ContentStyle) -> String
+    where
+        D: fmt::Display,
+    {
+        if self.color {
+            style.apply(content.to_string()).to_string()
+        } else {
+            content.to_string()
         }
     }
+}
 
-    let mut it = deps.iter().peekable();
-    while let Some(dependency) = it.next() {
-        levels_continue.push(it.peek().is_some());
-        print_package(
-            graph,
-            dependency,
-            format,
-            direction,
-            symbols,
-            prefix,
-            all,
-            visited_deps,
-            levels_continue,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::{graph, package};
+    use cargo_metadata::DependencyKind::{Development, Normal};
+
+    #[test]
+    fn dev_dependency_cycle() {
+        // a's tests depend on b, which depends on a
+        let graph = graph(
+            vec![
+                package("a", serde_json::json!({})),
+                package("b", serde_json::json!({})),
+            ],
+            &[("a", "b", Development), ("b", "a", Normal)],
         );
-        levels_continue.pop();
+        let format = Pattern::new("{p}").unwrap();
+
+        for prefix in &[Prefix::Indent, Prefix::Paths] {
+            for direction in &[EdgeDirection::Outgoing, EdgeDirection::Incoming] {
+                let printer = Printer {
+                    graph: &graph,
+                    format: &format,
+                    direction: *direction,
+                    symbols: &UTF8_SYMBOLS,
+                    prefix: *prefix,
+                    all: true,
+                    depth: None,
+                    color: false,
+                    duplicates: HashSet::new(),
+                    highlighted: HashSet::new(),
+                    ancestors: HashSet::new(),
+                    annotations: HashMap::new(),
+                    outdated: HashSet::new(),
+                };
+                printer.print_tree(graph.root.as_ref().unwrap());
+            }
+        }
     }
 }
@@ -0,0 +1,316 @@
+use crate::args::{Args, Charset};
+use crate::metadata;
+use crate::tree::{Symbols, ASCII_SYMBOLS, UTF8_SYMBOLS};
+use anyhow::{anyhow, Context, Error};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+
+#[derive(Deserialize)]
+struct UnitGraph {
+    version: u32,
+    units: Vec<Unit>,
+    roots: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct Unit {
+    pkg_id: String,
+    target: Target,
+    profile: Profile,
+    /// The target triple the unit is built for, or `None` for the host.
+    platform: Option<String>,
+    mode: String,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    dependencies: Vec<UnitDep>,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    kind: Vec<String>,
+    name: String,
+}
+
+#[derive(Deserialize, PartialEq)]
+struct Profile {
+    name: String,
+    opt_level: String,
+}
+
+#[derive(Deserialize)]
+struct UnitDep {
+    index: usize,
+}
+
+/// Prints the tree of compilation units Cargo would build, read from the output of
+/// `cargo build --unit-graph`.
+///
+/// Unlike the package graph, this shows each time a package is compiled separately, such as for
+/// the host and for the target, or with different features or profiles.
+pub fn print(args: &Args) -> Result<(), Error> {
+    let json = match args.unit_graph.iter().flatten().next() {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("error reading unit graph from {}", path.display()))?,
+        None => metadata::unit_graph(args)?,
+    };
+    let graph = serde_json::from_str::<UnitGraph>(&json).context("error parsing unit graph")?;
+    if graph.version != 1 {
+        return Err(anyhow!("unsupported unit graph version {}", graph.version));
+    }
+    for unit in &graph.units {
+        if let Some(dep) = unit
+            .dependencies
+            .iter()
+            .find(|d| d.index >= graph.units.len())
+        {
+            return Err(anyhow!("invalid unit index {} in unit graph", dep.index));
+        }
+    }
+
+    let symbols = match args.charset {
+        Charset::Utf8 => &UTF8_SYMBOLS,
+        Charset::Ascii => &ASCII_SYMBOLS,
+    };
+
+    let mut visited = HashSet::new();
+    for (i, root) in graph.roots.iter().enumerate() {
+        let unit = graph
+            .units
+            .get(*root)
+            .ok_or_else(|| anyhow!("invalid unit index {} in unit graph", root))?;
+
+        if i != 0 {
+            println!();
+        }
+        let printer = Printer {
+            graph: &graph,
+            symbols,
+            all: args.all,
+            depth: args.depth,
+            profile: &unit.profile,
+        };
+        printer.print_unit(*root, &mut visited, &mut vec![]);
+    }
+
+    print_duplicates(&graph);
+
+    Ok(())
+}
+
+struct Printer<'a> {
+    graph: &'a UnitGraph,
+    symbols: &'static Symbols,
+    all: bool,
+    depth: Option<usize>,
+    /// The profile of the root unit, which other units' profiles are only shown if they differ from.
+    profile: &'a Profile,
+}
+
+impl Printer<'_> {
+    fn print_unit(
+        &self,
+        idx: usize,
+        visited: &mut HashSet<usize>,
+        levels_continue: &mut Vec<bool>,
+    ) {
+        let new = self.all || visited.insert(idx);
+        let unit = &self.graph.units[idx];
+
+        if let Some((last_continues, rest)) = levels_continue.split_last() {
+            for continues in rest {
+                let c = if *continues { self.symbols.down } else { " " };
+                print!("{}   ", c);
+            }
+
+            let c = if *last_continues {
+                self.symbols.tee
+            } else {
+                self.symbols.ell
+            };
+            print!("{0}{1}{1} ", c, self.symbols.right);
+        }
+
+        print!("{}", self.label(unit));
+        if !new {
+            print!(" (*)");
+        }
+        println!();
+
+        if !new
+            || self
+                .depth
+                .is_some_and(|depth| levels_continue.len() >= depth)
+        {
+            return;
+        }
+
+        let deps = dependencies(self.graph, unit);
+        for (i, dep) in deps.iter().enumerate() {
+            levels_continue.push(i != deps.len() - 1);
+            self.print_unit(*dep, visited, levels_continue);
+            levels_continue.pop();
+        }
+    }
+
+    fn label(&self, unit: &Unit) -> String {
+        let mut label = format!("{} {}", package_label(&unit.pkg_id), describe(unit));
+        if unit.profile != *self.profile {
+            label.push_str(&format!(
+                " [profile: {}, opt-level {}]",
+                unit.profile.name, unit.profile.opt_level
+            ));
+        }
+        label
+    }
+}
+
+/// Returns the indices of the units a unit depends on, each once and in a consistent order.
+fn dependencies(graph: &UnitGraph, unit: &Unit) -> Vec<usize> {
+    let mut deps = unit
+        .dependencies
+        .iter()
+        .map(|d| d.index)
+        .collect::<Vec<_>>();
+    // units which sort the same are ordered by index so that duplicates end up next to each other
+    deps.sort_by_key(|idx| (sort_key(&graph.units[*idx]), *idx));
+    deps.dedup();
+    deps
+}
+
+/// Describes what is compiled for a unit and how, which distinguishes the units of one package.
+fn describe(unit: &Unit) -> String {
+    let mut parts = vec![unit.target.kind.join(", ")];
+    if unit.target.kind.iter().all(|kind| *kind != "lib") {
+        parts[0] = format!("{} \"{}\"", parts[0], unit.target.name);
+    }
+    if unit.mode != "build" {
+        parts.push(unit.mode.clone());
+    }
+    parts.push(match &unit.platform {
+        Some(platform) => platform.clone(),
+        None => "host".to_string(),
+    });
+    if !unit.features.is_empty() {
+        parts.push(format!("features: {}", unit.features.join(", ")));
+    }
+    format!("({})", parts.join("; "))
+}
+
+fn sort_key(unit: &Unit) -> (String, String, &str, &str, &Option<String>, &[String]) {
+    let (name, version) = parse_pkg_id(&unit.pkg_id);
+    (
+        name,
+        version,
+        &unit.target.name,
+        &unit.mode,
+        &unit.platform,
+        &unit.features,
+    )
+}
+
+/// Lists the packages which are compiled more than once, which is what the package graph hides.
+fn print_duplicates(graph: &UnitGraph) {
+    let mut compilations = BTreeMap::<_, Vec<&Unit>>::new();
+    for unit in &graph.units {
+        // a package's library is distinct from its build script or tests anyway
+        let key = (
+            parse_pkg_id(&unit.pkg_id),
+            &unit.target.name,
+            &unit.target.kind,
+            &unit.mode,
+        );
+        compilations.entry(key).or_default().push(unit);
+    }
+
+    let duplicates = compilations
+        .values()
+        .filter(|units| units.len() > 1)
+        .collect::<Vec<_>>();
+    if duplicates.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("compiled more than once:");
+    for units in duplicates {
+        println!("    {}", package_label(&units[0].pkg_id));
+        for unit in units {
+            print!("        {}", describe(unit));
+            if unit.profile != units[0].profile {
+                print!(
+                    " [profile: {}, opt-level {}]",
+                    unit.profile.name, unit.profile.opt_level
+                );
+            }
+            println!();
+        }
+    }
+}
+
+fn package_label(pkg_id: &str) -> String {
+    let (name, version) = parse_pkg_id(pkg_id);
+    format!("{} v{}", name, version)
+}
+
+/// Extracts the name and version from a package ID in either the `name version (source)` format of
+/// older Cargos or the `source#name@version` package ID spec format of newer ones.
+fn parse_pkg_id(pkg_id: &str) -> (String, String) {
+    if let Some((source, fragment)) = pkg_id.rsplit_once('#') {
+        return match fragment.split_once('@') {
+            Some((name, version)) => (name.to_string(), version.to_string()),
+            // the name defaults to the last path segment of the source
+            None => {
+                let path = source.split(['?', '#']).next().unwrap_or(source);
+                let name = path
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(path);
+                (name.to_string(), fragment.to_string())
+            }
+        };
+    }
+
+    let mut parts = pkg_id.split(' ');
+    let name = parts.next().unwrap_or(pkg_id);
+    let version = parts.next().unwrap_or("");
+    (name.to_string(), version.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_dependencies() {
+        let unit = |pkg_id: &str, dependencies: &[usize]| {
+            serde_json::json!({
+                "pkg_id": pkg_id,
+                "target": { "kind": ["lib"], "name": "lib" },
+                "profile": { "name": "dev", "opt_level": "0" },
+                "platform": null,
+                "mode": "build",
+                "dependencies": dependencies
+                    .iter()
+                    .map(|index| serde_json::json!({ "index": index }))
+                    .collect::<Vec<_>>(),
+            })
+        };
+        // units 1 and 2 are indistinguishable, and unit 1 is depended on twice around unit 2
+        let graph = serde_json::from_value::<UnitGraph>(serde_json::json!({
+            "version": 1,
+            "units": [
+                unit("root 0.1.0 (path+file:///ws/root)", &[1, 2, 1, 3]),
+                unit("foo 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)", &[]),
+                unit("foo 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)", &[]),
+                unit("bar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)", &[]),
+            ],
+            "roots": [0],
+        }))
+        .unwrap();
+
+        assert_eq!(dependencies(&graph, &graph.units[0]), [3, 1, 2]);
+    }
+}
@@ -0,0 +1,483 @@
+use crate::graph::Graph;
+use crate::size;
+use anyhow::{Context, Error};
+use cargo_metadata::{DependencyKind, Package, PackageId, Target};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Default)]
+pub struct Usage {
+    pub blocks: u64,
+    pub functions: u64,
+    pub impls: u64,
+    pub traits: u64,
+    /// Set if the crate root of every library, proc-macro and binary target contains
+    /// `#![forbid(unsafe_code)]`.
+    pub forbidden: bool,
+}
+
+impl Usage {
+    pub fn total(&self) -> u64 {
+        self.blocks + self.functions + self.impls + self.traits
+    }
+}
+
+/// Returns annotations with the unsafe code used by each package, along with a rollup of the
+/// unsafe code used by it and everything it depends on.
+pub fn annotations(graph: &Graph) -> Result<HashMap<&PackageId, String>, Error> {
+    let mut usages = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let package = &graph.graph[idx];
+        // the sources of packages which haven't been downloaded may not exist
+        if package.manifest_path.parent().unwrap().is_dir() {
+            let usage = scan_package(package)
+                .with_context(|| format!("error scanning sources of `{}`", package.name))?;
+            usages.insert(idx, usage);
+        }
+    }
+
+    let mut annotations = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        let usage = match usages.get(&idx) {
+            Some(usage) => usage,
+            None => {
+                annotations.insert(&graph.graph[idx].id, "unsafe: unknown".to_string());
+                continue;
+            }
+        };
+
+        let mut parts = vec![];
+        for (count, name) in &[
+            (usage.blocks, "block"),
+            (usage.functions, "fn"),
+            (usage.impls, "impl"),
+            (usage.traits, "trait"),
+        ] {
+            if *count > 0 {
+                let s = if *count == 1 { "" } else { "s" };
+                parts.push(format!("{} {}{}", count, name, s));
+            }
+        }
+        let summary = if usage.forbidden {
+            "forbidden".to_string()
+        } else if parts.is_empty() {
+            "none".to_string()
+        } else {
+            parts.join(", ")
+        };
+
+        let rollup = dependencies(graph, idx)
+            .iter()
+            .filter_map(|idx| usages.get(idx))
+            .map(Usage::total)
+            .sum::<u64>();
+
+        annotations.insert(
+            &graph.graph[idx].id,
+            format!("unsafe: {}; {} with dependencies", summary, rollup),
+        );
+    }
+
+    Ok(annotations)
+}
+
+/// Returns a package and everything it transitively depends on, excluding dev-dependencies.
+fn dependencies(graph: &Graph, idx: NodeIndex) -> HashSet<NodeIndex> {
+    let mut visited = HashSet::new();
+    let mut stack = vec![idx];
+
+    while let Some(idx) = stack.pop() {
+        if !visited.insert(idx) {
+            continue;
+        }
+
+        for edge in graph.graph.edges(idx) {
+            if *edge.weight() != DependencyKind::Development {
+                stack.push(edge.target());
+            }
+        }
+    }
+
+    visited
+}
+
+pub fn scan_package(package: &Package) -> Result<Usage, Error> {
+    let mut usage = Usage::default();
+
+    // build scripts, tests, benches and examples rarely forbid unsafe code, and aren't part of
+    // what dependents use anyway
+    let (targets, others) = package
+        .targets
+        .iter()
+        .partition::<Vec<_>, _>(|t| t.kind.iter().any(|k| is_shipped(k)));
+
+    for path in target_sources(&targets, &others)? {
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("error reading {}", path.display()))?;
+        scan(&tokenize(&source), &mut usage);
+    }
+
+    usage.forbidden = !targets.is_empty();
+    for target in targets {
+        let forbidden = match fs::read_to_string(&target.src_path) {
+            Ok(source) => forbids_unsafe(&tokenize(&source)),
+            Err(_) => false,
+        };
+        usage.forbidden &= forbidden;
+    }
+
+    Ok(usage)
+}
+
+/// Determines if a kind of target is built into what dependents or users of a package get.
+fn is_shipped(kind: &str) -> bool {
+    matches!(
+        kind,
+        "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro" | "bin"
+    )
+}
+
+/// Returns the Rust sources in the directories containing the crate roots of `targets`, leaving
+/// out those belonging to `others`.
+///
+/// Modules aren't followed from the crate roots, so this assumes a target's sources live beside
+/// or below its root, as they do in the standard layout.
+fn target_sources(targets: &[&Target], others: &[&Target]) -> Result<Vec<PathBuf>, Error> {
+    let mut dirs = targets
+        .iter()
+        .filter_map(|t| t.src_path.parent())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.dedup();
+    // a directory nested in another is scanned along with it
+    let roots = dirs
+        .iter()
+        .filter(|dir| !dirs.iter().any(|d| d != *dir && dir.starts_with(d)))
+        .collect::<Vec<_>>();
+
+    // the directories of other targets, like `tests` and `examples`, unless they hold one of our
+    // own crate roots as well
+    let excluded = others
+        .iter()
+        .filter_map(|t| t.src_path.parent())
+        .filter(|dir| !dirs.iter().any(|d| d.starts_with(dir)))
+        .collect::<Vec<_>>();
+
+    let mut sources = vec![];
+    for root in roots {
+        if !root.is_dir() {
+            continue;
+        }
+
+        for path in size::rust_sources(root)? {
+            if others.iter().any(|t| t.src_path == path)
+                || excluded.iter().any(|dir| path.starts_with(dir))
+            {
+                continue;
+            }
+            sources.push(path);
+        }
+    }
+
+    Ok(sources)
+}
+
+#[derive(PartialEq)]
+enum Token {
+    Ident(String),
+    Punct(char),
+}
+
+fn scan(tokens: &[Token], usage: &mut Usage) {
+    for (i, token) in tokens.iter().enumerate() {
+        if *token != Token::Ident("unsafe".to_string()) {
+            continue;
+        }
+
+        let mut rest = tokens[i + 1..].iter();
+        match rest.next() {
+            Some(Token::Punct('{')) => usage.blocks += 1,
+            Some(Token::Ident(ident)) => match &**ident {
+                "fn" => usage.functions += 1,
+                "impl" => usage.impls += 1,
+                "trait" | "auto" => usage.traits += 1,
+                // the ABI string has already been dropped from `unsafe extern "C" fn`
+                "extern" if rest.next() == Some(&Token::Ident("fn".to_string())) => {
+                    usage.functions += 1
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Looks for `#![forbid(unsafe_code)]`, possibly alongside other lints.
+fn forbids_unsafe(tokens: &[Token]) -> bool {
+    let prefix = [
+        Token::Punct('#'),
+        Token::Punct('!'),
+        Token::Punct('['),
+        Token::Ident("forbid".to_string()),
+        Token::Punct('('),
+    ];
+
+    tokens.windows(prefix.len()).enumerate().any(|(i, window)| {
+        window == prefix
+            && tokens[i + prefix.len()..]
+                .iter()
+                .take_while(|t| **t != Token::Punct(')'))
+                .any(|t| *t == Token::Ident("unsafe_code".to_string()))
+    })
+}
+
+/// Splits Rust source into identifiers and punctuation, dropping comments and literals so that
+/// `unsafe` appearing in them isn't counted.
+fn tokenize(source: &str) -> Vec<Token> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        } else if c == '"' {
+            i = skip_string(&chars, i + 1);
+        } else if c == '\'' {
+            if next == Some('\\') {
+                // skip the escaped character, which may itself be a quote
+                i += 3;
+                while i < chars.len() && chars[i] != '\'' {
+                    i += 1;
+                }
+                i += 1;
+            } else if chars.get(i + 2) == Some(&'\'') {
+                i += 3;
+            } else {
+                // a lifetime
+                i += 1;
+            }
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let ident = chars[start..i].iter().collect::<String>();
+
+            let raw = (ident == "r" || ident == "br")
+                && (chars.get(i) == Some(&'"') || chars.get(i) == Some(&'#'));
+            if raw {
+                let end = skip_raw_string(&chars, i);
+                if ident == "r" && end == i + 1 {
+                    // a raw identifier like `r#unsafe`, which isn't a keyword
+                    let start = end;
+                    i = end;
+                    while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                        i += 1;
+                    }
+                    let ident = chars[start..i].iter().collect::<String>();
+                    tokens.push(Token::Ident(format!("r#{}", ident)));
+                } else {
+                    i = end;
+                }
+            } else {
+                tokens.push(Token::Ident(ident));
+            }
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Skips past the end of a string literal whose opening quote has been consumed.
+fn skip_string(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    i
+}
+
+/// Skips past the end of a raw string literal, starting at its `#`s or opening quote.
+fn skip_raw_string(chars: &[char], mut i: usize) -> usize {
+    let mut hashes = 0;
+    while chars.get(i) == Some(&'#') {
+        hashes += 1;
+        i += 1;
+    }
+
+    // `r#ident` is a raw identifier rather than a string
+    if chars.get(i) != Some(&'"') {
+        return i;
+    }
+    i += 1;
+
+    while i < chars.len() {
+        let closing = chars[i + 1..].iter().take(hashes).filter(|c| **c == '#');
+        if chars[i] == '"' && closing.count() == hashes {
+            return i + 1 + hashes;
+        }
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(source: &str) -> (u64, u64, u64, u64) {
+        let mut usage = Usage::default();
+        scan(&tokenize(source), &mut usage);
+        (usage.blocks, usage.functions, usage.impls, usage.traits)
+    }
+
+    #[test]
+    fn counts() {
+        let cases = [
+            ("unsafe { f() }", (1, 0, 0, 0)),
+            ("unsafe fn f() {}", (0, 1, 0, 0)),
+            ("pub unsafe extern \"C\" fn f() {}", (0, 1, 0, 0)),
+            ("unsafe extern fn f() {}", (0, 1, 0, 0)),
+            ("unsafe extern \"C\" { fn f(); }", (0, 0, 0, 0)),
+            ("unsafe impl Send for T {}", (0, 0, 1, 0)),
+            ("unsafe trait T {}", (0, 0, 0, 1)),
+            ("unsafe auto trait T {}", (0, 0, 0, 1)),
+            ("// unsafe { f() }", (0, 0, 0, 0)),
+            ("/* unsafe { f() } */", (0, 0, 0, 0)),
+            ("/* /* nested */ unsafe { f() } */ unsafe {}", (1, 0, 0, 0)),
+            ("\"unsafe { f() }\"", (0, 0, 0, 0)),
+            ("\"\\\" unsafe {\" unsafe {}", (1, 0, 0, 0)),
+            ("r\"unsafe {\" unsafe {}", (1, 0, 0, 0)),
+            ("r#\"\" unsafe { \"#; unsafe {}", (1, 0, 0, 0)),
+            ("br##\"\"# unsafe {\"##", (0, 0, 0, 0)),
+            ("let c = '\\''; unsafe {}", (1, 0, 0, 0)),
+            ("let c = '\"'; unsafe {}", (1, 0, 0, 0)),
+            ("let c = '\\u{22}'; unsafe {}", (1, 0, 0, 0)),
+            ("fn f<'a>(x: &'a str) { unsafe {} }", (1, 0, 0, 0)),
+            ("let r#unsafe = 1; r#unsafe {}", (0, 0, 0, 0)),
+        ];
+
+        for (source, expected) in &cases {
+            assert_eq!(usage(source), *expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn forbids() {
+        let cases = [
+            ("#![forbid(unsafe_code)]", true),
+            ("#![forbid(missing_docs, unsafe_code)]\nfn f() {}", true),
+            ("#! [ forbid ( unsafe_code ) ]", true),
+            ("#![deny(unsafe_code)]", false),
+            ("#![forbid(missing_docs)]\nfn unsafe_code() {}", false),
+            ("#[forbid(unsafe_code)]\nfn f() {}", false),
+            ("// #![forbid(unsafe_code)]", false),
+            ("fn f() {}", false),
+        ];
+
+        for (source, expected) in &cases {
+            assert_eq!(forbids_unsafe(&tokenize(source)), *expected, "{}", source);
+        }
+    }
+
+    /// Lays out a package with the given targets in a temporary directory, and returns the
+    /// sources scanned relative to it.
+    fn scanned(name: &str, targets: &[(&str, &str)]) -> Vec<String> {
+        let root =
+            std::env::temp_dir().join(format!("cargo-tree-unsafe-{}-{}", std::process::id(), name));
+        let mut files = targets.iter().map(|(_, path)| *path).collect::<Vec<_>>();
+        files.extend(&["src/foo/mod.rs", "tests/common/mod.rs"]);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let targets = targets
+            .iter()
+            .map(|(kind, path)| {
+                serde_json::from_value::<Target>(serde_json::json!({
+                    "name": name,
+                    "kind": [kind],
+                    "src_path": root.join(path),
+                }))
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let (targets, others) = targets
+            .iter()
+            .partition::<Vec<_>, _>(|t| is_shipped(&t.kind[0]));
+
+        let mut sources = target_sources(&targets, &others)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.strip_prefix(&root).unwrap().to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        sources.sort();
+
+        fs::remove_dir_all(root).unwrap();
+        sources
+    }
+
+    #[test]
+    fn sources() {
+        let standard = [
+            ("lib", "src/lib.rs"),
+            ("bin", "src/main.rs"),
+            ("bin", "src/bin/tool.rs"),
+            ("test", "tests/it.rs"),
+            ("example", "examples/ex.rs"),
+            ("bench", "benches/b.rs"),
+            ("custom-build", "build.rs"),
+        ];
+        assert_eq!(
+            scanned("standard", &standard),
+            [
+                "src/bin/tool.rs",
+                "src/foo/mod.rs",
+                "src/lib.rs",
+                "src/main.rs"
+            ]
+        );
+
+        let flat = [
+            ("lib", "lib.rs"),
+            ("test", "tests/it.rs"),
+            ("custom-build", "build.rs"),
+        ];
+        assert_eq!(scanned("flat", &flat), ["lib.rs", "src/foo/mod.rs"]);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::args::Args;
+use crate::format::Pattern;
+use crate::graph::Graph;
+use crate::tree;
+use anyhow::Error;
+use petgraph::algo::dominators;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use std::collections::HashMap;
+
+/// Prints the direct dependencies of the root package, ordered by the number of packages which
+/// would leave the graph if each were removed.
+pub fn print(args: &Args, graph: &Graph) -> Result<(), Error> {
+    let format = Pattern::new(&args.format)?;
+
+    let root = graph.nodes[tree::find_root(args, graph)?];
+    let weights = weigh(graph, root);
+
+    println!(
+        "{} ({} dependencies, {} shared)",
+        format.display(&graph.graph[root]),
+        weights.total,
+        weights.shared
+    );
+    for (weight, idx) in weights.direct {
+        println!("{:>6} {}", weight, format.display(&graph.graph[idx]));
+    }
+
+    Ok(())
+}
+
+/// How the packages below a root are divided between its direct dependencies.
+struct Weights {
+    /// The direct dependencies, heaviest first, with the number of packages exclusive to each.
+    direct: Vec<(usize, NodeIndex)>,
+    /// The number of packages the root depends on.
+    total: usize,
+    /// The number of packages pulled in by more than one direct dependency.
+    shared: usize,
+}
+
+fn weigh(graph: &Graph, root: NodeIndex) -> Weights {
+    // Each edge out of the root is split by a node standing for that direct dependency, so a
+    // package is exclusive to a direct dependency exactly when that node dominates it. Packages
+    // which are direct dependencies but are also pulled in elsewhere are correctly left with no
+    // exclusive weight of their own.
+    let mut dominance = DiGraph::<Option<NodeIndex>, ()>::new();
+    let mut nodes = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        nodes.insert(idx, dominance.add_node(Some(idx)));
+    }
+
+    let mut direct = HashMap::new();
+    for edge in (&graph.graph).edge_references() {
+        let source = nodes[&edge.source()];
+        let target = nodes[&edge.target()];
+
+        if edge.source() == root {
+            let split = *direct.entry(edge.target()).or_insert_with(|| {
+                let split = dominance.add_node(None);
+                dominance.add_edge(split, target, ());
+                split
+            });
+            dominance.update_edge(source, split, ());
+        } else {
+            dominance.add_edge(source, target, ());
+        }
+    }
+
+    let dominators = dominators::simple_fast(&dominance, nodes[&root]);
+
+    let mut weights = direct
+        .values()
+        .map(|split| (*split, 0))
+        .collect::<HashMap<_, _>>();
+    let mut shared = 0;
+    for (idx, node) in &nodes {
+        if *idx == root || dominators.immediate_dominator(*node).is_none() {
+            continue;
+        }
+
+        let mut current = *node;
+        loop {
+            match dominators.immediate_dominator(current) {
+                Some(dominator) if dominance[dominator].is_none() => {
+                    *weights.get_mut(&dominator).unwrap() += 1;
+                    break;
+                }
+                Some(dominator) if dominator == nodes[&root] => {
+                    shared += 1;
+                    break;
+                }
+                Some(dominator) => current = dominator,
+                None => break,
+            }
+        }
+    }
+
+    let mut direct = direct
+        .iter()
+        .map(|(idx, split)| (weights[split], *idx))
+        .collect::<Vec<_>>();
+    direct.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| graph.graph[a.1].id.cmp(&graph.graph[b.1].id))
+    });
+
+    let total = nodes
+        .values()
+        .filter(|n| dominators.immediate_dominator(**n).is_some())
+        .count();
+
+    Weights {
+        direct,
+        total,
+        shared,
+    }
+}
+
+/// Returns, for each package reachable from the root, the packages which would leave the graph
+/// along with it, including itself.
+pub fn dominated(graph: &Graph, root: NodeIndex) -> HashMap<NodeIndex, Vec<NodeIndex>> {
+    let dominators = dominators::simple_fast(&graph.graph, root);
+
+    let mut dominated = HashMap::new();
+    for idx in graph.graph.node_indices() {
+        if idx != root && dominators.immediate_dominator(idx).is_none() {
+            continue;
+        }
+
+        let mut current = Some(idx);
+        while let Some(dominator) = current {
+            dominated
+                .entry(dominator)
+                .or_insert_with(Vec::new)
+                .push(idx);
+            current = dominators.immediate_dominator(dominator);
+        }
+    }
+
+    dominated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::{graph, node, package};
+    use cargo_metadata::DependencyKind::Normal;
+
+    #[test]
+    fn weights() {
+        // c is shared by a and b, and b is a direct dependency also pulled in by e
+        let graph = graph(
+            ["root", "a", "b", "c", "d", "e", "f"]
+                .iter()
+                .map(|name| package(name, serde_json::json!({})))
+                .collect(),
+            &[
+                ("root", "a", Normal),
+                ("root", "b", Normal),
+                ("root", "e", Normal),
+                ("a", "c", Normal),
+                ("a", "d", Normal),
+                ("b", "c", Normal),
+                ("e", "b", Normal),
+                ("e", "f", Normal),
+            ],
+        );
+
+        let weights = weigh(&graph, node(&graph, "root"));
+        let direct = weights
+            .direct
+            .iter()
+            .map(|(weight, idx)| (&*graph.graph[*idx].name, *weight))
+            .collect::<Vec<_>>();
+        assert_eq!(direct, [("a", 2), ("e", 2), ("b", 0)]);
+        assert_eq!(weights.total, 6);
+        assert_eq!(weights.shared, 2);
+    }
+
+    #[test]
+    fn dominated_packages() {
+        let graph = graph(
+            ["root", "a", "b", "c"]
+                .iter()
+                .map(|name| package(name, serde_json::json!({})))
+                .collect(),
+            &[
+                ("root", "a", Normal),
+                ("root", "b", Normal),
+                ("a", "c", Normal),
+                ("b", "c", Normal),
+            ],
+        );
+
+        let dominated = dominated(&graph, node(&graph, "root"));
+        let names = |name| {
+            let mut names = dominated[&node(&graph, name)]
+                .iter()
+                .map(|idx| &*graph.graph[*idx].name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(names("root"), ["a", "b", "c", "root"]);
+        assert_eq!(names("a"), ["a"]);
+        assert_eq!(names("c"), ["c"]);
+    }
+}